@@ -1,60 +1,327 @@
-use std::fs;
+use std::{cmp::Reverse, collections::BinaryHeap, env, fs};
+
+/// What to do with lessons given in clock time that wrap past midnight.
+/// Selected with the command line argument `--midnight=split` or
+/// `--midnight=reject` (the default).
+enum Midnight {
+    /// Split the lesson into one part before and one after midnight. A
+    /// student has to attend both parts, so they are always scheduled
+    /// together. Each part gets its own room, though.
+    Split,
+
+    /// Refuse to process the input
+    Reject,
+}
+
+impl Midnight {
+    fn from_args() -> Self {
+        let mut result = Midnight::Reject;
+        for arg in env::args().skip(1) {
+            result = match arg.as_str() {
+                "--midnight=split" => Midnight::Split,
+                "--midnight=reject" => Midnight::Reject,
+                _ => panic!("Unknown argument: {arg}"),
+            };
+        }
+        result
+    }
+}
+
+/// Number of seconds in a day
+const DAY: u64 = 24 * 60 * 60;
 
 #[derive(Clone, Copy)]
-enum Event {
-    Start(u64, u64),
-    End(u64),
+struct Lesson {
+    /// The line in the input this lesson comes from. Lessons split at
+    /// midnight share the same id.
+    id: usize,
+    start: u64,
+    end: u64,
+    weight: u64,
+}
+
+/// Parses a time given either as a raw integer or as a clock time in the form
+/// `HH:MM` or `HH:MM:SS`. Clock times are normalised to seconds since
+/// midnight. Raw integers have no unit and are returned unchanged, so an input
+/// must not mix both formats. The second return value specifies if the time
+/// was a clock time.
+fn parse_time(s: &str) -> (u64, bool) {
+    if !s.contains(':') {
+        return (s.parse::<u64>().unwrap(), false);
+    }
+
+    let parts = s
+        .split(':')
+        .map(|p| p.parse::<u64>().unwrap())
+        .collect::<Vec<_>>();
+    let (h, m, sec) = match parts[..] {
+        [h, m] => (h, m, 0),
+        [h, m, sec] => (h, m, sec),
+        _ => panic!("Invalid clock time: {s}"),
+    };
+    let t = h * 3600 + m * 60 + sec;
+    if m >= 60 || sec >= 60 || t > DAY {
+        panic!("Invalid clock time: {s}");
+    }
+    (t, true)
+}
+
+/// Computes the maximum number of lessons a single student can attend and
+/// returns the indices of the chosen lessons in chronological order
+fn max_schedule(lessons: &[Lesson]) -> Vec<usize> {
+    // Greedily pick the lesson that finishes earliest. This does not change
+    // the number of lessons we can visit but always leaves us the most time
+    // for the remaining ones.
+    let mut order = (0..lessons.len()).collect::<Vec<_>>();
+    order.sort_unstable_by_key(|&i| (lessons[i].end, lessons[i].start));
+
+    let mut result = Vec::new();
+    let mut curr_end = 0;
+    for i in order {
+        if result.is_empty() || lessons[i].start >= curr_end {
+            result.push(i);
+            curr_end = lessons[i].end;
+        }
+    }
+    result
+}
+
+/// Assigns every lesson to a room so that no two lessons in the same room
+/// overlap. Returns the room of each lesson and the number of rooms used,
+/// which is the maximum number of lessons running in parallel. A lesson that
+/// ends exactly when another one starts does not run in parallel with it, so
+/// both can share a room. A single lesson still needs one room.
+fn assign_rooms(lessons: &[Lesson]) -> (Vec<usize>, usize) {
+    let mut order = (0..lessons.len()).collect::<Vec<_>>();
+    order.sort_unstable_by_key(|&i| (lessons[i].start, lessons[i].end));
+
+    // rooms that are currently occupied, ordered by the end of their lesson
+    let mut busy = BinaryHeap::new();
+    let mut free = Vec::new();
+    let mut n_rooms = 0;
+    let mut rooms = vec![0; lessons.len()];
+
+    for i in order {
+        // a lesson may start in a room as soon as the previous one has ended
+        while let Some(&Reverse((end, room))) = busy.peek() {
+            if end > lessons[i].start {
+                break;
+            }
+            busy.pop();
+            free.push(room);
+        }
+        let room = free.pop().unwrap_or_else(|| {
+            n_rooms += 1;
+            n_rooms - 1
+        });
+        rooms[i] = room;
+        busy.push(Reverse((lessons[i].end, room)));
+    }
+
+    (rooms, n_rooms)
+}
+
+/// Computes the maximum total weight of a set of non-overlapping lessons and
+/// returns it together with the indices of the chosen lessons in
+/// chronological order
+fn max_weighted_schedule(lessons: &[Lesson]) -> (u64, Vec<usize>) {
+    let mut order = (0..lessons.len()).collect::<Vec<_>>();
+    order.sort_unstable_by_key(|&i| (lessons[i].end, lessons[i].start));
+    let ends = order.iter().map(|&i| lessons[i].end).collect::<Vec<_>>();
+
+    // dp[j] is the best total weight we can get from the first j lessons (in
+    // order of their end). Lesson j can be combined with all lessons that end
+    // before it starts, which we find with a binary search.
+    let mut dp = vec![0; order.len() + 1];
+    let mut prev = vec![0; order.len()];
+    for (j, &i) in order.iter().enumerate() {
+        prev[j] = ends.partition_point(|&e| e <= lessons[i].start).min(j);
+        dp[j + 1] = dp[j].max(dp[prev[j]] + lessons[i].weight);
+    }
+
+    // walk back through the table to find out which lessons we've taken
+    let mut result = Vec::new();
+    let mut j = order.len();
+    while j > 0 {
+        if dp[j] == dp[j - 1] {
+            j -= 1;
+        } else {
+            result.push(order[j - 1]);
+            j = prev[j - 1];
+        }
+    }
+    result.reverse();
+
+    (dp[order.len()], result)
+}
+
+/// Finds the best schedule when some lessons may have been split at midnight.
+/// `schedule` computes the best schedule for lessons that have not been split
+/// and `value` is the value of a single lesson.
+///
+/// Both parts of a split lesson have to be taken together. Any two split
+/// lessons overlap at midnight, so a schedule contains at most one of them and
+/// all other lessons have to fit between its two parts. We solve the case
+/// without any split lesson and the case for every split lesson separately
+/// and keep the best one. Returns the total value, in which every lesson is
+/// only counted once, and the indices of the chosen lessons in chronological
+/// order.
+fn schedule_across_midnight(
+    lessons: &[Lesson],
+    schedule: impl Fn(&[Lesson]) -> Vec<usize>,
+    value: impl Fn(&Lesson) -> u64,
+) -> (u64, Vec<usize>) {
+    // the parts of a split lesson are consecutive and share the same id
+    let is_split = |i: usize| {
+        (i > 0 && lessons[i - 1].id == lessons[i].id)
+            || (i + 1 < lessons.len() && lessons[i + 1].id == lessons[i].id)
+    };
+    let plain = (0..lessons.len())
+        .filter(|&i| !is_split(i))
+        .collect::<Vec<_>>();
+
+    // solve the problem for a subset of the plain lessons
+    let solve = |subset: Vec<usize>| {
+        let sub_lessons = subset.iter().map(|&i| lessons[i]).collect::<Vec<_>>();
+        schedule(&sub_lessons)
+            .into_iter()
+            .map(|j| subset[j])
+            .collect::<Vec<_>>()
+    };
+    let total = |chosen: &[usize]| chosen.iter().map(|&i| value(&lessons[i])).sum::<u64>();
+
+    let mut best = solve(plain.clone());
+    let mut best_value = total(&best);
+
+    // the part before midnight comes first in the list of lessons
+    for i in (0..lessons.len()).filter(|&i| is_split(i) && lessons[i].end == DAY) {
+        let (before, after) = (lessons[i], lessons[i + 1]);
+        let between = plain
+            .iter()
+            .copied()
+            .filter(|&j| lessons[j].start >= after.end && lessons[j].end <= before.start)
+            .collect();
+        let inner = solve(between);
+        let v = total(&inner) + value(&before);
+        let mut chosen = vec![i + 1];
+        chosen.extend(inner);
+        chosen.push(i);
+        if v > best_value {
+            best = chosen;
+            best_value = v;
+        }
+    }
+
+    (best_value, best)
+}
+
+/// Checks that no two lessons assigned to the same room overlap
+fn validate_rooms(lessons: &[Lesson], rooms: &[usize], n_rooms: usize) -> bool {
+    let mut by_room = vec![Vec::new(); n_rooms];
+    for (l, &r) in lessons.iter().zip(rooms) {
+        if r >= n_rooms {
+            return false;
+        }
+        by_room[r].push(*l);
+    }
+    by_room.iter_mut().all(|ls| {
+        ls.sort_unstable_by_key(|l| (l.start, l.end));
+        ls.windows(2).all(|w| w[0].end <= w[1].start)
+    })
 }
 
 fn main() {
+    let midnight = Midnight::from_args();
     let input = fs::read_to_string("input.txt").expect("Could not read file");
-    let mut events = Vec::new();
-    for l in input.lines() {
-        let mut parts = l.split_ascii_whitespace();
-        let start = parts.nth(6).unwrap().parse::<u64>().unwrap();
-        let end = parts.last().unwrap().parse::<u64>().unwrap();
-        events.push(Event::Start(start, end));
-        events.push(Event::End(end));
-    }
-    events.sort_unstable_by_key(|&e| match e {
-        Event::Start(time, _) => time,
-        Event::End(time) => time,
-    });
-
-    // Solve both parts simultaneously. For part 1, we start a lesson but switch
-    // immediately to another one if it finishes earlier as the one we're
-    // currently in. This does not change the number of lesson we've visited but
-    // gives us more time. For part 2, we simply compute the maximum number of
-    // lessons that run in parallel at any given time.
-
-    let mut total1 = 1;
-    let mut total2 = 0;
-
-    let mut curr_end = if let Event::Start(_, end) = events[0] {
-        end
-    } else {
-        unreachable!()
-    };
-    let mut curr_sum = 1;
-
-    for &e in events.iter().skip(1) {
-        match e {
-            Event::Start(start, end) => {
-                if start < curr_end {
-                    curr_end = curr_end.min(end);
-                } else {
-                    total1 += 1;
-                    curr_end = end;
-                }
-                curr_sum += 1;
-                total2 = total2.max(curr_sum);
+    let mut lessons = Vec::new();
+    let mut weighted = false;
+
+    // raw integers have no unit, so they cannot be compared with clock times
+    let mut clock = None;
+    for (id, l) in input.lines().enumerate() {
+        let mut parts = l.split_ascii_whitespace().collect::<Vec<_>>();
+
+        // lessons may optionally be followed by their weight (e.g. `w=3`)
+        let mut weight = 1;
+        if let Some(w) = parts.last().unwrap().strip_prefix("w=") {
+            weight = w.parse::<u64>().unwrap();
+            weighted = true;
+            parts.pop();
+        }
+
+        let (start, start_clock) = parse_time(parts[6]);
+        let (mut end, end_clock) = parse_time(parts.last().unwrap());
+        for c in [start_clock, end_clock] {
+            if *clock.get_or_insert(c) != c {
+                panic!("Lessons must not mix clock times and raw integers: {l}");
             }
-            Event::End(_) => {
-                curr_sum -= 1;
+        }
+        if start_clock && end == 0 && start > 0 {
+            // the lesson ends exactly at midnight and does not wrap past it
+            end = DAY;
+        }
+        if start_clock && end < start {
+            match midnight {
+                Midnight::Split => {
+                    lessons.push(Lesson {
+                        id,
+                        start,
+                        end: DAY,
+                        weight,
+                    });
+                    lessons.push(Lesson {
+                        id,
+                        start: 0,
+                        end,
+                        weight,
+                    });
+                }
+                Midnight::Reject => panic!("Lesson wraps past midnight: {l}"),
             }
+        } else {
+            lessons.push(Lesson {
+                id,
+                start,
+                end,
+                weight,
+            });
         }
     }
 
+    let (total1, schedule) = schedule_across_midnight(&lessons, max_schedule, |_| 1);
+    let (rooms, n_rooms) = assign_rooms(&lessons);
+    assert!(validate_rooms(&lessons, &rooms, n_rooms));
+
+    let total2 = n_rooms;
+
     println!("{total1}");
     println!("{total2}");
+
+    println!();
+    println!("Schedule:");
+    for &i in &schedule {
+        println!(
+            "  lesson {}: {}-{}",
+            lessons[i].id, lessons[i].start, lessons[i].end
+        );
+    }
+    if weighted {
+        let (value, chosen) =
+            schedule_across_midnight(&lessons, |ls| max_weighted_schedule(ls).1, |l| l.weight);
+        println!("Weighted schedule (total weight {value}):");
+        for &i in &chosen {
+            println!(
+                "  lesson {}: {}-{} (w={})",
+                lessons[i].id, lessons[i].start, lessons[i].end, lessons[i].weight
+            );
+        }
+    }
+    println!("Rooms:");
+    let mut by_room = vec![Vec::new(); n_rooms];
+    for (l, &r) in lessons.iter().zip(&rooms) {
+        by_room[r].push(l.id.to_string());
+    }
+    for (r, ls) in by_room.iter().enumerate() {
+        println!("  room {r}: {}", ls.join(", "));
+    }
 }
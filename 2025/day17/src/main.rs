@@ -1,63 +1,338 @@
-use std::fs;
+use std::{cmp::Reverse, env, fs};
 
-use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_hash::FxHashMap;
 
-fn longest_chain(
-    start: u64,
-    graph: &FxHashMap<u64, Vec<u64>>,
-    cache: &mut FxHashMap<u64, u64>,
-) -> u64 {
-    if let Some(c) = cache.get(&start) {
-        return *c;
+/// What to do in part 1 if the graph contains cycles. Part 2 works on the
+/// condensation graph and is not affected. Selected with the command line
+/// argument `--cycles=error` (the default) or `--cycles=count-distinct`.
+enum Cycles {
+    /// Refuse to compute the longest chain
+    Error,
+
+    /// Collapse every cycle into a single node and count each distinct node
+    /// of a cycle once
+    CountDistinct,
+}
+
+impl Cycles {
+    fn from_args() -> Self {
+        let mut result = Cycles::Error;
+        for arg in env::args().skip(1) {
+            result = match arg.as_str() {
+                "--cycles=error" => Cycles::Error,
+                "--cycles=count-distinct" => Cycles::CountDistinct,
+                _ => panic!("Unknown argument: {arg}"),
+            };
+        }
+        result
+    }
+}
+
+/// Computes the strongly connected components of the given graph with
+/// Tarjan's algorithm. Returns the component of each node and the number of
+/// components. Components are numbered in reverse topological order, i.e.
+/// edges between components always point from a higher to a lower number.
+fn scc(edges: &[Vec<usize>]) -> (Vec<usize>, usize) {
+    let n = edges.len();
+    let mut index = vec![usize::MAX; n];
+    let mut lowlink = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut comp = vec![usize::MAX; n];
+    let mut stack = Vec::new();
+    let mut n_comps = 0;
+    let mut next_index = 0;
+
+    // explicit call stack of (node, index of next edge to visit) so that long
+    // chains do not overflow the real one
+    let mut calls = Vec::new();
+    for root in 0..n {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        calls.push((root, 0));
+        while let Some(&mut (v, ref mut ei)) = calls.last_mut() {
+            if *ei == 0 {
+                index[v] = next_index;
+                lowlink[v] = next_index;
+                next_index += 1;
+                stack.push(v);
+                on_stack[v] = true;
+            }
+
+            if let Some(&w) = edges[v].get(*ei) {
+                *ei += 1;
+                if index[w] == usize::MAX {
+                    calls.push((w, 0));
+                } else if on_stack[w] {
+                    lowlink[v] = lowlink[v].min(index[w]);
+                }
+                continue;
+            }
+
+            // all edges visited
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                lowlink[parent] = lowlink[parent].min(lowlink[v]);
+            }
+            if lowlink[v] == index[v] {
+                loop {
+                    let w = stack.pop().unwrap();
+                    on_stack[w] = false;
+                    comp[w] = n_comps;
+                    if w == v {
+                        break;
+                    }
+                }
+                n_comps += 1;
+            }
+        }
+    }
+
+    (comp, n_comps)
+}
+
+/// Computes a minimum set of edges that makes the given directed acyclic graph
+/// strongly connected using the algorithm by Eswaran and Tarjan
+fn strong_connect(dag: &[Vec<usize>]) -> Vec<(usize, usize)> {
+    let n = dag.len();
+    if n <= 1 {
+        return Vec::new();
+    }
+
+    let mut has_incoming = vec![false; n];
+    for &w in dag.iter().flatten() {
+        has_incoming[w] = true;
+    }
+    let sources = (0..n).filter(|&v| !has_incoming[v]).collect::<Vec<_>>();
+    let n_sinks = dag.iter().filter(|e| e.is_empty()).count();
+
+    // the algorithm below requires at most as many sources as sinks, so
+    // reverse the graph if necessary
+    if sources.len() > n_sinks {
+        let mut reversed = vec![Vec::new(); n];
+        for (v, e) in dag.iter().enumerate() {
+            for &w in e {
+                reversed[w].push(v);
+            }
+        }
+        return strong_connect(&reversed)
+            .into_iter()
+            .map(|(a, b)| (b, a))
+            .collect();
+    }
+
+    // find a maximal set of pairs of sources and sinks so that each source
+    // can reach its sink. Each node is visited at most once, so every source
+    // and sink that remains unmatched is connected to a matched pair.
+    let mut visited = vec![false; n];
+    let mut matched = vec![false; n];
+    let mut s = Vec::new();
+    let mut t = Vec::new();
+    for &source in &sources {
+        if visited[source] {
+            continue;
+        }
+        visited[source] = true;
+        let mut stack = vec![(source, 0)];
+        while let Some(&mut (v, ref mut ei)) = stack.last_mut() {
+            if dag[v].is_empty() {
+                s.push(source);
+                t.push(v);
+                matched[source] = true;
+                matched[v] = true;
+                break;
+            }
+            if let Some(&w) = dag[v].get(*ei) {
+                *ei += 1;
+                if !visited[w] {
+                    visited[w] = true;
+                    stack.push((w, 0));
+                }
+            } else {
+                stack.pop();
+            }
+        }
     }
-    let mut result = 1;
-    if let Some(neighbors) = graph.get(&start) {
-        for n in neighbors {
-            result = result.max(1 + longest_chain(*n, graph, cache));
+
+    // append unmatched sources and sinks
+    let p = s.len();
+    s.extend(sources.iter().filter(|&&v| !matched[v]));
+    t.extend((0..n).filter(|&v| dag[v].is_empty() && !matched[v]));
+    let a = s.len();
+    let b = t.len();
+
+    // connect the matched pairs to a cycle, let each unmatched source be
+    // reached by an unmatched sink, and chain the remaining sinks into the
+    // cycle
+    let mut result = Vec::new();
+    for i in 0..p - 1 {
+        result.push((t[i], s[i + 1]));
+    }
+    for i in p..a {
+        result.push((t[i], s[i]));
+    }
+    if a < b {
+        result.push((t[p - 1], t[a]));
+        for i in a..b - 1 {
+            result.push((t[i], t[i + 1]));
         }
+        result.push((t[b - 1], s[0]));
+    } else {
+        result.push((t[p - 1], s[0]));
     }
-    cache.insert(start, result);
     result
 }
 
+/// Computes the longest chain in the given directed acyclic graph, where the
+/// nodes must be numbered in reverse topological order (see [`scc`]). Each node
+/// contributes its weight to the length of the chain. Returns the length and
+/// the nodes of the chain. If there are several chains with the same length,
+/// the one whose nodes have the smallest keys is chosen, starting with the
+/// first node.
+fn longest_chain(dag: &[Vec<usize>], weights: &[u64], keys: &[u64]) -> (u64, Vec<usize>) {
+    // all successors of a node have a lower number, so we can process the
+    // nodes in ascending order
+    let mut best = vec![0; dag.len()];
+    let mut next = vec![None; dag.len()];
+    for v in 0..dag.len() {
+        next[v] = dag[v]
+            .iter()
+            .copied()
+            .max_by_key(|&w| (best[w], Reverse(keys[w])));
+        best[v] = weights[v] + next[v].map_or(0, |w| best[w]);
+    }
+
+    let Some(start) = (0..dag.len()).max_by_key(|&v| (best[v], Reverse(keys[v]))) else {
+        return (0, Vec::new());
+    };
+    let mut chain = vec![start];
+    while let Some(w) = next[*chain.last().unwrap()] {
+        chain.push(w);
+    }
+    (best[start], chain)
+}
+
 fn main() {
+    let cycles_policy = Cycles::from_args();
     let input = fs::read_to_string("input.txt").expect("Could not read file");
 
-    let mut graph: FxHashMap<u64, Vec<u64>> = FxHashMap::default();
-    let mut all_nodes = FxHashSet::default();
-    let mut destinations = FxHashSet::default();
+    // map node names to consecutive indices
+    let mut ids: FxHashMap<u64, usize> = FxHashMap::default();
+    let mut names = Vec::new();
+    let mut edges: Vec<Vec<usize>> = Vec::new();
+    let mut id = |name: u64, edges: &mut Vec<Vec<usize>>| {
+        *ids.entry(name).or_insert_with(|| {
+            names.push(name);
+            edges.push(Vec::new());
+            edges.len() - 1
+        })
+    };
     for l in input.lines() {
         let (from, to) = l.split_once(" -> ").unwrap();
-        let from = from.parse::<u64>().unwrap();
-        let to = to.parse::<u64>().unwrap();
-        graph.entry(from).or_default().push(to);
-        all_nodes.insert(from);
-        all_nodes.insert(to);
-        destinations.insert(to);
-    }
-
-    // part 1
-    let mut cache = FxHashMap::default();
-    println!(
-        "{}",
-        graph
-            .keys()
-            .map(|from| longest_chain(*from, &graph, &mut cache))
-            .max()
-            .unwrap()
-    );
-
-    // part 2 - the number of additional edges required to make a graph strongly
-    // connected is max(N - O, N - I) where N is the total number of nodes, O is
-    // the number of nodes that have outgoing edges, and I is the number of
-    // nodes having incoming edges.
+        let from = id(from.parse::<u64>().unwrap(), &mut edges);
+        let to = id(to.parse::<u64>().unwrap(), &mut edges);
+        edges[from].push(to);
+    }
+    let n = edges.len();
+
+    // find cycles
+    let (comp, n_comps) = scc(&edges);
+    let mut members = vec![Vec::new(); n_comps];
+    for v in 0..n {
+        members[comp[v]].push(v);
+    }
+    let cycles = members
+        .iter()
+        .filter(|m| m.len() > 1 || edges[m[0]].contains(&m[0]))
+        .collect::<Vec<_>>();
+    let cycles_error = if matches!(cycles_policy, Cycles::Error) && !cycles.is_empty() {
+        let cycles = cycles
+            .iter()
+            .map(|m| {
+                let m = m.iter().map(|&v| names[v].to_string()).collect::<Vec<_>>();
+                format!("[{}]", m.join(", "))
+            })
+            .collect::<Vec<_>>();
+        Some(format!("Graph contains cycles: {}", cycles.join(", ")))
+    } else {
+        None
+    };
+
+    // build condensation graph where each strongly connected component is
+    // weighted by the number of distinct nodes it contains
+    let mut condensed = vec![Vec::new(); n_comps];
+    for (v, e) in edges.iter().enumerate() {
+        for &w in e {
+            if comp[v] != comp[w] {
+                condensed[comp[v]].push(comp[w]);
+            }
+        }
+    }
+    for c in &mut condensed {
+        c.sort_unstable();
+        c.dedup();
+    }
+    let weights = members.iter().map(|m| m.len() as u64).collect::<Vec<_>>();
+
+    // part 1 - report the cycles instead of the length of the longest chain
+    // if we must not collapse them
+    let keys = members
+        .iter()
+        .map(|m| m.iter().map(|&v| names[v]).min().unwrap())
+        .collect::<Vec<_>>();
+    let (total1, chain) = longest_chain(&condensed, &weights, &keys);
+    match &cycles_error {
+        Some(e) => println!("{e}"),
+        None => println!("{total1}"),
+    }
+
+    // part 2 - the minimum number of additional edges required to make a
+    // graph strongly connected is max(S, T) where S is the number of sources
+    // and T is the number of sinks in the condensation graph (or 0 if the
+    // graph is already strongly connected). This works for any graph, so it
+    // does not depend on the cycle policy of part 1.
     //
     // Note that the problem statement contains an error: The edges given in
     // part 2 to make the graph strongly connected never connect to 0, so no
     // node can ever reach 0! One possible way to make the graph in part 2
     // strongly connected would instead be: 6->4, 7->0, 5->1
-    println!(
-        "{}",
-        (all_nodes.len() - graph.len()).max(all_nodes.len() - destinations.len())
-    );
+    let new_edges = strong_connect(&condensed)
+        .into_iter()
+        .map(|(a, b)| (members[a][0], members[b][0]))
+        .collect::<Vec<_>>();
+    println!("{}", new_edges.len());
+
+    // check that the graph is actually strongly connected now
+    let mut augmented = edges.clone();
+    for &(a, b) in &new_edges {
+        augmented[a].push(b);
+    }
+    assert_eq!(scc(&augmented).1, 1);
+
+    // print the nodes of the longest chain. Nodes of the same cycle are listed
+    // together in parentheses.
+    println!();
+    if cycles_error.is_none() {
+        let chain_start = chain[0];
+        let chain = chain
+            .iter()
+            .map(|&c| {
+                let mut m = members[c].iter().map(|&v| names[v]).collect::<Vec<_>>();
+                m.sort_unstable();
+                let m = m.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                if m.len() == 1 {
+                    m[0].clone()
+                } else {
+                    format!("({})", m.join(", "))
+                }
+            })
+            .collect::<Vec<_>>();
+        println!("Longest chain (starting at {}):", keys[chain_start]);
+        println!("  {}", chain.join(" -> "));
+    }
+
+    println!("Edges to add:");
+    for &(a, b) in &new_edges {
+        println!("  {} -> {}", names[a], names[b]);
+    }
 }
@@ -1,54 +1,476 @@
-use std::fs;
+use std::{cmp::Reverse, env, fs};
 
-fn matches(pattern: &str, string: &str) -> bool {
-    let bp = pattern.as_bytes();
-    let bs = string.as_bytes();
+use rustc_hash::FxHashSet;
 
-    for (&p, &s) in bp.iter().zip(bs.iter()) {
-        if p != s && p != b'?' {
-            return false;
+use crate::{
+    ntt::{MAX_LEN, MODULI, convolve},
+    pattern::Pattern,
+    shiftand::find_all_matches,
+};
+
+mod ntt;
+mod pattern;
+mod shiftand;
+
+/// Finds all positions `i` where `string` fits `pattern[i..]`, treating `?` in
+/// the pattern as wildcard. For each position, we compute the sum of
+/// `(p - s)^2 * p * s` over all aligned characters, where wildcards are 0. All
+/// terms are non-negative, so the string fits iff the sum is 0. The sums are
+/// computed with three convolutions in O((n + m) log n). We evaluate them
+/// modulo two primes whose product exceeds the largest possible sum. The
+/// length of the pattern and the string together must not exceed
+/// [`MAX_LEN`].
+fn find_matches(pattern: &[u8], string: &[u8]) -> Vec<bool> {
+    if string.len() > pattern.len() {
+        return Vec::new();
+    }
+    if string.is_empty() {
+        return vec![true; pattern.len() + 1];
+    }
+
+    let p = pattern
+        .iter()
+        .map(|&c| if c == b'?' { 0 } else { c as u64 })
+        .collect::<Vec<_>>();
+    let s = string.iter().rev().map(|&c| c as u64).collect::<Vec<_>>();
+    let pow = |v: &[u64], e: u32| v.iter().map(|x| x.pow(e)).collect::<Vec<_>>();
+
+    let mut result = vec![true; pattern.len() - string.len() + 1];
+    for m in MODULI {
+        let p3s = convolve(&pow(&p, 3), &s, m);
+        let p2s2 = convolve(&pow(&p, 2), &pow(&s, 2), m);
+        let ps3 = convolve(&p, &pow(&s, 3), m);
+        for (i, r) in result.iter_mut().enumerate() {
+            let k = i + string.len() - 1;
+            let sum = (p3s[k] + ps3[k] + 2 * (m - p2s2[k])) % m;
+            *r &= sum == 0;
+        }
+    }
+    result
+}
+
+/// Finds all points where string `b` can overlap string `a` if it is placed
+/// after it, i.e. all shifts `i` for which `a[i..] == b[..a.len() - i]`. The
+/// strings must share at least two characters and `b` must extend past the end
+/// of `a`. Uses the prefix function of `b` followed by `a` to find all suffixes
+/// of `a` that are also prefixes of `b` in O(n).
+fn overlap_points(a: &[u8], b: &[u8]) -> Vec<usize> {
+    // use a separator that does not appear in any of the strings
+    let s = b
+        .iter()
+        .map(|&c| c as u16)
+        .chain([256])
+        .chain(a.iter().map(|&c| c as u16))
+        .collect::<Vec<_>>();
+    let n = s.len();
+    let mut pi = vec![0; n];
+    for i in 1..n {
+        let mut k = pi[i - 1];
+        while k > 0 && s[i] != s[k] {
+            k = pi[k - 1];
+        }
+        if s[i] == s[k] {
+            k += 1;
         }
+        pi[i] = k;
     }
 
-    true
+    // every border of the concatenated string corresponds to an overlap point
+    let mut result = Vec::new();
+    let mut l = pi[n - 1];
+    while l > 1 {
+        if l < a.len() && l < b.len() {
+            result.push(a.len() - l);
+        }
+        l = pi[l - 1];
+    }
+    result
+}
+
+/// The result of [`pack`]
+struct Packing {
+    /// The maximum number of strings that can be placed
+    count: usize,
+
+    /// One optimal placement as a list of start offsets and the indices of
+    /// the strings placed there
+    offsets: Vec<(usize, usize)>,
+
+    /// The number of optimal placements (saturates at `u128::MAX`). Only
+    /// computed if requested.
+    n_optimal: Option<u128>,
+}
+
+/// Computes the maximum number of strings that can be placed into a pattern
+/// of length `n`, starting with one at position 0. `fits[s][i]` specifies if
+/// string `s` fits the pattern at position `i`, `lens[s]` is the string's
+/// length, and `overlaps[s][t]` contains the overlap points of strings `s` and
+/// `t`. If `count_optimal` is set, we also count the number of optimal
+/// placements.
+fn pack(
+    n: usize,
+    fits: &[Vec<bool>],
+    lens: &[usize],
+    overlaps: &[Vec<Vec<usize>>],
+    count_optimal: bool,
+) -> Packing {
+    // bottom-up DP: For each position in the pattern (from the back to the
+    // front) and each string, determine the maximum number of strings we can
+    // place if we start with this string. For this, we need to look up the
+    // maximum at each overlap point and all indices that follow the string.
+    // We remember which position and string we've chosen next.
+    let k = fits.len();
+    let mut dp = vec![vec![0; n + 1]; k];
+    let mut next = vec![vec![None; n + 1]; k];
+    let mut max = vec![0; n + 1]; // performance: maintain running maximum
+    let mut max_pos = vec![None; n + 1]; // the first position having the maximum
+    let mut cnt = vec![vec![0u128; n + 1]; k];
+    let mut max_cnt = vec![0u128; n + 1]; // number of ways to reach the maximum
+    for i in (0..n).rev() {
+        max[i] = max[i + 1];
+        max_pos[i] = max_pos[i + 1];
+        max_cnt[i] = max_cnt[i + 1];
+
+        for s in 0..k {
+            if !fits[s][i] {
+                continue;
+            }
+
+            let mut best = 0;
+            for (t, points) in overlaps[s].iter().enumerate() {
+                for &j in points {
+                    if i + j < n && dp[t][i + j] > best {
+                        best = dp[t][i + j];
+                        next[s][i] = Some((i + j, t));
+                    }
+                }
+            }
+            let after = (i + lens[s]).min(n);
+            if max[after] > best {
+                best = max[after];
+                next[s][i] = max_pos[after];
+            }
+            dp[s][i] = best + 1;
+
+            if count_optimal {
+                cnt[s][i] = if best == 0 {
+                    1
+                } else {
+                    let mut c = 0u128;
+                    for (t, points) in overlaps[s].iter().enumerate() {
+                        for &j in points {
+                            if i + j < n && dp[t][i + j] == best {
+                                c = c.saturating_add(cnt[t][i + j]);
+                            }
+                        }
+                    }
+                    if max[after] == best {
+                        c = c.saturating_add(max_cnt[after]);
+                    }
+                    c
+                };
+            }
+        }
+
+        // update running maximum (prefer the first string at the earliest
+        // position)
+        for s in 0..k {
+            if dp[s][i] == 0 {
+                continue;
+            }
+            if dp[s][i] > max[i] {
+                max[i] = dp[s][i];
+                max_pos[i] = Some((i, s));
+                max_cnt[i] = cnt[s][i];
+            } else if dp[s][i] == max[i] {
+                if max_pos[i].is_none_or(|(p, _)| p > i) {
+                    max_pos[i] = Some((i, s));
+                }
+                max_cnt[i] = max_cnt[i].saturating_add(cnt[s][i]);
+            }
+        }
+    }
+
+    // follow the back-pointers, starting with the best string at position 0
+    let start = (0..k)
+        .filter(|&s| dp[s][0] > 0)
+        .max_by_key(|&s| (dp[s][0], Reverse(s)));
+    let mut offsets = Vec::new();
+    let mut pos = start.map(|s| (0, s));
+    while let Some((p, s)) = pos {
+        offsets.push((p, s));
+        pos = next[s][p];
+    }
+
+    Packing {
+        count: start.map_or(0, |s| dp[s][0]),
+        offsets,
+        n_optimal: count_optimal.then(|| {
+            let best = start.map_or(0, |s| dp[s][0]);
+            (0..k)
+                .filter(|&s| best > 0 && dp[s][0] == best)
+                .fold(0u128, |acc, s| acc.saturating_add(cnt[s][0]))
+        }),
+    }
+}
+
+/// Renders the pattern and the placed copies of the strings below it. Copies
+/// that do not overlap share the same line.
+fn render(pattern: &Pattern, strings: &[&[u8]], offsets: &[(usize, usize)]) -> String {
+    let mut result = (0..pattern.len())
+        .map(|i| pattern.display_char(i))
+        .collect::<String>();
+    result.push('\n');
+
+    // offsets are sorted, so we can greedily put each copy into the first line
+    // where the previous copy has already ended (with at least one space in
+    // between)
+    let mut lines: Vec<Vec<u8>> = Vec::new();
+    for &(o, s) in offsets {
+        let line = match lines.iter_mut().find(|l| l.len() < o) {
+            Some(l) => l,
+            None => {
+                lines.push(Vec::new());
+                lines.last_mut().unwrap()
+            }
+        };
+        line.resize(o, b' ');
+        line.extend_from_slice(strings[s]);
+    }
+    for l in lines {
+        result.push_str(&String::from_utf8_lossy(&l));
+        result.push('\n');
+    }
+    result
 }
 
 fn main() {
+    // `--count-optimal` additionally counts the number of optimal placements
+    // in part 2
+    let mut count_optimal = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--count-optimal" => count_optimal = true,
+            _ => panic!("Unknown argument: {arg}"),
+        }
+    }
+
     let input = fs::read_to_string("input.txt").expect("Could not read file");
 
-    let (pattern, string) = input.split_once("\n\n").unwrap();
+    let (pattern, strings) = input.split_once("\n\n").unwrap();
     let pattern = &pattern[9..].trim();
-    let string = &string[8..].trim();
 
-    // part 1
-    println!("{}", pattern.lines().filter(|l| matches(l, string)).count());
+    // there may be several strings - one per line
+    let mut strings = strings[8..]
+        .lines()
+        .map(|l| l.trim().as_bytes())
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>();
+    let mut seen = FxHashSet::default();
+    strings.retain(|s| seen.insert(*s));
+
+    let compile =
+        |p: &str| Pattern::compile(p).unwrap_or_else(|e| panic!("Invalid pattern `{p}`: {e}"));
+
+    // part 1 - count the lines matching at least one of the strings
+    println!(
+        "{}",
+        pattern
+            .lines()
+            .map(compile)
+            .filter(|p| strings.iter().any(|s| p.matches(s)))
+            .count()
+    );
+
+    // part 2 - here, every token of the pattern must match exactly one
+    // character. Use the FFT-based matcher if we only have a single string,
+    // the pattern only contains `?`, and both fit into a transform. Otherwise,
+    // find the matches of all strings in a single scan.
+    let pattern = compile(&pattern.replace('\n', ""));
+    if pattern.has_star() {
+        eprintln!("Part 2 does not support `*` in the pattern");
+        return;
+    }
+    let fits =
+        if strings.len() == 1 && pattern.is_simple() && pattern.len() + strings[0].len() <= MAX_LEN
+        {
+            let mut f = find_matches(pattern.source(), strings[0]);
+            f.resize(pattern.len(), false);
+            vec![f]
+        } else {
+            find_all_matches(&pattern, &strings)
+        };
+
+    let overlaps = strings
+        .iter()
+        .map(|a| strings.iter().map(|b| overlap_points(a, b)).collect())
+        .collect::<Vec<_>>();
+    let lens = strings.iter().map(|s| s.len()).collect::<Vec<_>>();
+
+    let packing = pack(pattern.len(), &fits, &lens, &overlaps, count_optimal);
+    println!("{}", packing.count);
+
+    println!();
+    if let Some(n) = packing.n_optimal {
+        println!("Number of optimal placements: {n}");
+    }
+    println!("Placements: {:?}", packing.offsets);
+    print!("{}", render(&pattern, &strings, &packing.offsets));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::matches;
+
+    /// Generates a pseudo-random string over the given alphabet
+    fn random_bytes(seed: &mut u64, len: usize, alphabet: &[u8]) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                alphabet[(*seed >> 33) as usize % alphabet.len()]
+            })
+            .collect()
+    }
 
-    // part 2
-    let pattern = pattern.replace('\n', "");
+    /// Generates a pseudo-random length between 1 and `max`
+    fn random_len(seed: &mut u64, max: usize) -> usize {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (*seed >> 33) as usize % max + 1
+    }
 
-    // find all points where the string can overlap itself
-    let mut overlap_points = Vec::new();
-    for i in 1..string.len() - 1 {
-        if string[i..] == string[0..string.len() - i] {
-            overlap_points.push(i);
+    /// Generates a pseudo-random string over a small alphabet (including `?`)
+    /// so that matches are frequent
+    fn random_string(seed: &mut u64, len: usize) -> Vec<u8> {
+        random_bytes(seed, len, b"ab?")
+    }
+
+    #[test]
+    fn find_matches_agrees_with_reference() {
+        let mut seed = 1;
+        for _ in 0..500 {
+            let n = (seed >> 40) as usize % 20 + 1;
+            let pattern = random_string(&mut seed, n);
+            let m = (seed >> 40) as usize % n + 1;
+            let string = random_string(&mut seed, m);
+            let fits = find_matches(&pattern, &string);
+            assert_eq!(fits.len(), n - m + 1);
+            for (i, &f) in fits.iter().enumerate() {
+                assert_eq!(f, matches(&pattern[i..], &string), "{pattern:?} {string:?}");
+            }
         }
     }
 
-    // bottom-up DP: For each position in the pattern (from the back to the
-    // front), determine the maximum number of strings we can place. For this,
-    // we need to look up the maximum at each overlap point and all indices that
-    // follow the string.
-    let mut dp = vec![0; pattern.len()];
-    let mut max = vec![0; pattern.len()]; // performance: maintain running maximum
-    for i in (0..=pattern.len() - string.len()).rev() {
-        if matches(&pattern[i..i + string.len()], string) {
-            dp[i] = overlap_points.iter().map(|&j| dp[i + j]).max().unwrap();
-            if i + string.len() < dp.len() {
-                dp[i] = dp[i].max(max[i + string.len()]);
-            }
-            dp[i] += 1;
-        }
-        max[i] = max[i + 1].max(dp[i]);
-    }
-    println!("{}", dp[0]);
+    #[test]
+    fn question_mark_in_string_is_literal() {
+        assert_eq!(find_matches(b"abab", b"a?"), vec![false; 3]);
+        assert_eq!(find_matches(b"a?ab", b"a?"), vec![true, false, false]);
+    }
+
+    #[test]
+    fn overlap_points_agrees_with_reference() {
+        let mut seed = 2;
+        for _ in 0..1000 {
+            let (la, lb) = (random_len(&mut seed, 12), random_len(&mut seed, 12));
+            let a = random_bytes(&mut seed, la, b"ab");
+            let b = random_bytes(&mut seed, lb, b"ab");
+
+            // compare every suffix of `a` with the prefix of `b`
+            let expected = (1..a.len())
+                .filter(|&i| {
+                    let l = a.len() - i;
+                    l >= 2 && l < b.len() && a[i..] == b[..l]
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(overlap_points(&a, &b), expected, "{a:?} {b:?}");
+        }
+    }
+
+    /// Checks if string `t` may follow string `s` when they are placed at
+    /// positions `p` and `q`: Either they do not overlap, or they share at
+    /// least two characters and `t` extends past the end of `s`
+    fn may_follow(strings: &[Vec<u8>], (p, s): (usize, usize), (q, t): (usize, usize)) -> bool {
+        let (a, b) = (&strings[s], &strings[t]);
+        if q >= p + a.len() {
+            return true;
+        }
+        let l = p + a.len() - q;
+        q > p && l >= 2 && l < b.len() && a[q - p..] == b[..l]
+    }
+
+    /// Enumerates all placements that start with the given string and returns
+    /// the maximum number of strings and the number of placements reaching it
+    fn brute_pack(strings: &[Vec<u8>], fits: &[Vec<bool>], first: (usize, usize)) -> (usize, u128) {
+        let mut best = (1, 1);
+        for q in first.0 + 1..fits[0].len() {
+            for t in 0..strings.len() {
+                if !fits[t][q] || !may_follow(strings, first, (q, t)) {
+                    continue;
+                }
+                let (count, n) = brute_pack(strings, fits, (q, t));
+                if count + 1 > best.0 {
+                    best = (count + 1, n);
+                } else if count + 1 == best.0 {
+                    best.1 += n;
+                }
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn pack_agrees_with_brute_force() {
+        let mut seed = 3;
+        for _ in 0..300 {
+            let n = random_len(&mut seed, 14);
+            let pattern = random_string(&mut seed, n);
+            let mut strings = Vec::new();
+            for _ in 0..random_len(&mut seed, 3) {
+                let len = random_len(&mut seed, 4);
+                let s = random_bytes(&mut seed, len, b"ab");
+                if !strings.contains(&s) {
+                    strings.push(s);
+                }
+            }
+
+            let fits = strings
+                .iter()
+                .map(|s| {
+                    (0..n)
+                        .map(|i| i + s.len() <= n && matches(&pattern[i..], s))
+                        .collect()
+                })
+                .collect::<Vec<Vec<_>>>();
+            let overlaps = strings
+                .iter()
+                .map(|a| strings.iter().map(|b| overlap_points(a, b)).collect())
+                .collect::<Vec<_>>();
+            let lens = strings.iter().map(|s| s.len()).collect::<Vec<_>>();
+            let packing = pack(n, &fits, &lens, &overlaps, true);
+
+            let mut expected = (0, 0);
+            for s in (0..strings.len()).filter(|&s| fits[s][0]) {
+                let (count, k) = brute_pack(&strings, &fits, (0, s));
+                if count > expected.0 {
+                    expected = (count, k);
+                } else if count == expected.0 {
+                    expected.1 += k;
+                }
+            }
+            assert_eq!(packing.count, expected.0);
+            assert_eq!(packing.n_optimal, Some(expected.1));
+
+            // the placement must be valid and optimal
+            assert_eq!(packing.offsets.len(), packing.count);
+            if let Some(&(p, _)) = packing.offsets.first() {
+                assert_eq!(p, 0);
+            }
+            for &(p, s) in &packing.offsets {
+                assert!(fits[s][p]);
+            }
+            for w in packing.offsets.windows(2) {
+                assert!(may_follow(&strings, w[0], w[1]));
+            }
+        }
+    }
 }
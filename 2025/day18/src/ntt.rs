@@ -0,0 +1,90 @@
+/// Prime moduli of the form c * 2^k + 1 that all have 3 as primitive root
+pub const MODULI: [u64; 2] = [998_244_353, 167_772_161];
+
+/// The maximum length of a transform. 998244353 = 119 * 2^23 + 1 only has
+/// roots of unity up to the order 2^23.
+pub const MAX_LEN: usize = 1 << 23;
+
+fn pow_mod(mut b: u64, mut e: u64, m: u64) -> u64 {
+    let mut result = 1;
+    b %= m;
+    while e > 0 {
+        if e & 1 == 1 {
+            result = result * b % m;
+        }
+        b = b * b % m;
+        e >>= 1;
+    }
+    result
+}
+
+/// In-place number theoretic transform of `a` modulo `m`. The length of `a`
+/// must be a power of two.
+fn ntt(a: &mut [u64], invert: bool, m: u64) {
+    let n = a.len();
+
+    // bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j ^= bit;
+        if i < j {
+            a.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let mut w = pow_mod(3, (m - 1) / len as u64, m);
+        if invert {
+            w = pow_mod(w, m - 2, m);
+        }
+        for chunk in a.chunks_mut(len) {
+            let (lo, hi) = chunk.split_at_mut(len / 2);
+            let mut wn = 1;
+            for (u, v) in lo.iter_mut().zip(hi.iter_mut()) {
+                let x = *u;
+                let y = *v * wn % m;
+                *u = (x + y) % m;
+                *v = (x + m - y) % m;
+                wn = wn * w % m;
+            }
+        }
+        len <<= 1;
+    }
+
+    if invert {
+        let n_inv = pow_mod(n as u64, m - 2, m);
+        for x in a.iter_mut() {
+            *x = *x * n_inv % m;
+        }
+    }
+}
+
+/// Computes the convolution of `a` and `b` modulo `m`, which must be one of
+/// [`MODULI`]
+pub fn convolve(a: &[u64], b: &[u64], m: u64) -> Vec<u64> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let result_len = a.len() + b.len() - 1;
+    let n = result_len.next_power_of_two();
+    assert!(n <= MAX_LEN, "Convolution is too long for the NTT");
+
+    let mut fa = a.iter().map(|&x| x % m).collect::<Vec<_>>();
+    let mut fb = b.iter().map(|&x| x % m).collect::<Vec<_>>();
+    fa.resize(n, 0);
+    fb.resize(n, 0);
+    ntt(&mut fa, false, m);
+    ntt(&mut fb, false, m);
+    for (x, y) in fa.iter_mut().zip(&fb) {
+        *x = *x * y % m;
+    }
+    ntt(&mut fa, true, m);
+    fa.truncate(result_len);
+    fa
+}
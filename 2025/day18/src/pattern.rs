@@ -0,0 +1,227 @@
+use std::fmt;
+
+/// A single element of a compiled pattern
+#[derive(Clone)]
+pub enum Token {
+    /// Matches exactly this byte
+    Byte(u8),
+
+    /// `?` - matches any single byte
+    Any,
+
+    /// `[abc]` or `[^abc]` - matches any single byte whose bit is set
+    Class([u64; 4]),
+
+    /// `*` - matches any run of bytes, including an empty one
+    Star,
+}
+
+impl Token {
+    /// Checks if this token matches the given byte. Must not be called on
+    /// [`Token::Star`].
+    pub fn matches(&self, c: u8) -> bool {
+        match self {
+            Token::Byte(b) => *b == c,
+            Token::Any => true,
+            Token::Class(set) => set[c as usize / 64] & (1 << (c % 64)) != 0,
+            Token::Star => unreachable!(),
+        }
+    }
+}
+
+/// An error that occurred while compiling a pattern
+#[derive(Debug)]
+pub enum PatternError {
+    /// A character class starting at the given position was not closed
+    UnclosedClass(usize),
+
+    /// The character class starting at the given position does not contain
+    /// any characters
+    EmptyClass(usize),
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::UnclosedClass(pos) => {
+                write!(f, "unclosed character class at position {pos}")
+            }
+            PatternError::EmptyClass(pos) => write!(f, "empty character class at position {pos}"),
+        }
+    }
+}
+
+/// A compiled pattern
+pub struct Pattern {
+    source: Vec<u8>,
+    tokens: Vec<Token>,
+}
+
+impl Pattern {
+    /// Compiles a pattern consisting of literal bytes, `?`, character classes
+    /// (`[abc]`), negated character classes (`[^abc]`), and `*`
+    pub fn compile(pattern: &str) -> Result<Self, PatternError> {
+        let bp = pattern.as_bytes();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < bp.len() {
+            match bp[i] {
+                b'?' => tokens.push(Token::Any),
+                b'*' => tokens.push(Token::Star),
+                b'[' => {
+                    let start = i;
+                    let negated = bp.get(i + 1) == Some(&b'^');
+                    if negated {
+                        i += 1;
+                    }
+                    let len = bp[i + 1..]
+                        .iter()
+                        .position(|&c| c == b']')
+                        .ok_or(PatternError::UnclosedClass(start))?;
+                    if len == 0 {
+                        return Err(PatternError::EmptyClass(start));
+                    }
+                    let mut set = [0; 4];
+                    for &c in &bp[i + 1..i + 1 + len] {
+                        set[c as usize / 64] |= 1 << (c % 64);
+                    }
+                    if negated {
+                        set = set.map(|s| !s);
+                    }
+                    tokens.push(Token::Class(set));
+                    i += len + 1;
+                }
+                c => tokens.push(Token::Byte(c)),
+            }
+            i += 1;
+        }
+
+        Ok(Self {
+            source: bp.to_vec(),
+            tokens,
+        })
+    }
+
+    /// Returns the number of tokens in this pattern
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Returns the token at the given index
+    pub fn token(&self, i: usize) -> &Token {
+        &self.tokens[i]
+    }
+
+    /// Returns a character representing the token at the given index.
+    /// Character classes are displayed as `#`.
+    pub fn display_char(&self, i: usize) -> char {
+        match self.tokens[i] {
+            Token::Byte(b) => b as char,
+            Token::Any => '?',
+            Token::Class(_) => '#',
+            Token::Star => '*',
+        }
+    }
+
+    /// Checks if the pattern only consists of literal bytes and `?`
+    pub fn is_simple(&self) -> bool {
+        self.tokens
+            .iter()
+            .all(|t| matches!(t, Token::Byte(_) | Token::Any))
+    }
+
+    /// Checks if the pattern contains `*`
+    pub fn has_star(&self) -> bool {
+        self.tokens.iter().any(|t| matches!(t, Token::Star))
+    }
+
+    /// Returns the original pattern
+    pub fn source(&self) -> &[u8] {
+        &self.source
+    }
+
+    /// Checks if the string matches this pattern. Patterns without `*` are
+    /// compared with the string token by token until one of them ends, so
+    /// adding a character class does not change how a line is matched.
+    /// Patterns with `*` must match the whole string.
+    pub fn matches(&self, string: &[u8]) -> bool {
+        if self.has_star() {
+            self.matches_glob(string)
+        } else if self.is_simple() {
+            matches(&self.source, string)
+        } else {
+            self.tokens.iter().zip(string).all(|(t, &c)| t.matches(c))
+        }
+    }
+
+    /// Matches the whole string against the pattern. If we hit a mismatch, we
+    /// backtrack to the last `*` and let it consume one more byte.
+    fn matches_glob(&self, string: &[u8]) -> bool {
+        let mut ti = 0;
+        let mut si = 0;
+        let mut last_star = None;
+        while si < string.len() {
+            match self.tokens.get(ti) {
+                Some(Token::Star) => {
+                    last_star = Some((ti, si));
+                    ti += 1;
+                }
+                Some(t) if t.matches(string[si]) => {
+                    ti += 1;
+                    si += 1;
+                }
+                _ => {
+                    let Some((star_ti, star_si)) = last_star else {
+                        return false;
+                    };
+                    ti = star_ti + 1;
+                    si = star_si + 1;
+                    last_star = Some((star_ti, star_si + 1));
+                }
+            }
+        }
+        self.tokens[ti..].iter().all(|t| matches!(t, Token::Star))
+    }
+}
+
+/// Checks if the string fits the pattern at its beginning by comparing them
+/// byte by byte, treating `?` in the pattern as wildcard. This is the backend
+/// for simple patterns and the reference implementation for
+/// [`crate::find_matches`].
+pub fn matches(pattern: &[u8], string: &[u8]) -> bool {
+    for (&p, &s) in pattern.iter().zip(string.iter()) {
+        if p != s && p != b'?' {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(pattern: &str, string: &str) -> bool {
+        Pattern::compile(pattern)
+            .unwrap()
+            .matches(string.as_bytes())
+    }
+
+    #[test]
+    fn classes_use_the_same_length_rule_as_simple_patterns() {
+        assert!(check("ab", "abc"));
+        assert!(check("a[b]", "abc"));
+        assert!(check("a?", "abc"));
+        assert!(check("a[^c]c", "ab"));
+        assert!(!check("a[^b]", "abc"));
+    }
+
+    #[test]
+    fn star_matches_the_whole_string() {
+        assert!(check("a*", "abc"));
+        assert!(check("a*c", "abc"));
+        assert!(!check("a*b", "abc"));
+        assert!(check("*[bc]", "abc"));
+    }
+}
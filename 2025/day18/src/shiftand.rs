@@ -0,0 +1,152 @@
+use crate::pattern::{Pattern, Token};
+
+/// A bit set with a fixed number of bits
+#[derive(Clone)]
+struct Bits(Vec<u64>);
+
+impl Bits {
+    fn new(n: usize) -> Self {
+        Self(vec![0; n.div_ceil(64)])
+    }
+
+    fn set(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.0[i / 64] & (1 << (i % 64)) != 0
+    }
+}
+
+/// Finds all positions where each of the given strings fits the pattern with
+/// the multi-pattern Shift-And algorithm. All strings are concatenated to a
+/// single bit vector, so we only need one scan over the pattern, which takes
+/// O(n * m / 64) where m is the total length of all strings. The pattern must
+/// not contain `*`.
+///
+/// Returns one vector per string that specifies for each token of the pattern
+/// whether the string fits there.
+pub fn find_all_matches(pattern: &Pattern, strings: &[&[u8]]) -> Vec<Vec<bool>> {
+    let total = strings.iter().map(|s| s.len()).sum::<usize>();
+    let mut starts = Bits::new(total);
+    let mut ends = Vec::new();
+
+    // masks[c] has a bit set for each position in the concatenated strings
+    // that contains the byte c
+    let mut masks = vec![Bits::new(total); 256];
+    let mut offset = 0;
+    for s in strings {
+        starts.set(offset);
+        for (i, &c) in s.iter().enumerate() {
+            masks[c as usize].set(offset + i);
+        }
+        offset += s.len();
+        ends.push(offset - 1);
+    }
+    let any = Bits(vec![u64::MAX; total.div_ceil(64)]);
+
+    let mut result = vec![vec![false; pattern.len()]; strings.len()];
+    let mut state = Bits::new(total);
+    let mut mask = Bits::new(total);
+    for t in 0..pattern.len() {
+        // determine which positions in the strings the current token matches
+        let token = pattern.token(t);
+        let m = match token {
+            Token::Byte(c) => &masks[*c as usize],
+            Token::Any => &any,
+            Token::Star => unreachable!(),
+            Token::Class(_) => {
+                mask.0.fill(0);
+                for (c, cm) in masks.iter().enumerate() {
+                    if token.matches(c as u8) {
+                        for (w, &x) in mask.0.iter_mut().zip(&cm.0) {
+                            *w |= x;
+                        }
+                    }
+                }
+                &mask
+            }
+        };
+
+        // state = ((state << 1) | starts) & m
+        let mut carry = 0;
+        for ((w, &s), &x) in state.0.iter_mut().zip(&starts.0).zip(&m.0) {
+            let next_carry = *w >> 63;
+            *w = ((*w << 1) | carry | s) & x;
+            carry = next_carry;
+        }
+
+        for (si, &e) in ends.iter().enumerate() {
+            if state.get(e) {
+                result[si][t + 1 - strings[si].len()] = true;
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::matches;
+
+    /// Generates a pseudo-random number below `n`
+    fn random(seed: &mut u64, n: usize) -> usize {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (*seed >> 33) as usize % n
+    }
+
+    /// Generates pseudo-random strings and a pattern made of the given pieces
+    fn random_input(seed: &mut u64, pieces: &[&str]) -> (String, Vec<Vec<u8>>) {
+        let pattern = (0..random(seed, 40) + 1)
+            .map(|_| pieces[random(seed, pieces.len())])
+            .collect::<String>();
+        let strings = (0..random(seed, 5) + 1)
+            .map(|_| {
+                (0..random(seed, 30) + 1)
+                    .map(|_| b"ab"[random(seed, 2)])
+                    .collect()
+            })
+            .collect();
+        (pattern, strings)
+    }
+
+    #[test]
+    fn agrees_with_reference_for_simple_patterns() {
+        let mut seed = 4;
+        for _ in 0..500 {
+            let (source, strings) = random_input(&mut seed, &["a", "b", "?"]);
+            let pattern = Pattern::compile(&source).unwrap();
+            let strings = strings.iter().map(|s| s.as_slice()).collect::<Vec<_>>();
+            let fits = find_all_matches(&pattern, &strings);
+            for (s, f) in strings.iter().zip(&fits) {
+                for (i, &fit) in f.iter().enumerate() {
+                    let expected =
+                        i + s.len() <= source.len() && matches(&source.as_bytes()[i..], s);
+                    assert_eq!(fit, expected, "{source} {s:?} {i}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn agrees_with_reference_for_classes() {
+        let mut seed = 5;
+        for _ in 0..500 {
+            let (source, strings) = random_input(&mut seed, &["a", "?", "[ab]", "[^a]", "[b]"]);
+            let pattern = Pattern::compile(&source).unwrap();
+            let strings = strings.iter().map(|s| s.as_slice()).collect::<Vec<_>>();
+            let fits = find_all_matches(&pattern, &strings);
+            for (s, f) in strings.iter().zip(&fits) {
+                for (i, &fit) in f.iter().enumerate() {
+                    let expected = i + s.len() <= pattern.len()
+                        && s.iter()
+                            .enumerate()
+                            .all(|(j, &c)| pattern.token(i + j).matches(c));
+                    assert_eq!(fit, expected, "{source} {s:?} {i}");
+                }
+            }
+        }
+    }
+}
@@ -1,15 +1,46 @@
 use std::fs;
 
+use dashu_int::UBig;
+use rayon::prelude::*;
+
+/// The result of a sweep from one corner of the grid to the opposite one
+struct Sweep {
+    /// The maximum sum of a path from the start corner to each cell (`None` if
+    /// the cell cannot be reached)
+    best: Vec<Option<u64>>,
+
+    /// The cell we came from on the best path to each cell (`None` for the
+    /// start corner)
+    prev: Vec<Option<usize>>,
+}
+
+impl Sweep {
+    /// Follows the back-pointers from the given cell to the start corner and
+    /// returns the indices of all cells on the way
+    fn path_from(&self, mut cell: usize) -> Vec<usize> {
+        let mut result = vec![cell];
+        while let Some(p) = self.prev[cell] {
+            result.push(p);
+            cell = p;
+        }
+        result
+    }
+}
+
+/// Computes the maximum sum of a path from the start corner to every cell,
+/// moving only in the directions `dx` and `dy`. Walls (`None` in the grid)
+/// cannot be passed.
 fn dp(
-    grid: &[u64],
+    grid: &[Option<u64>],
     width: usize,
     height: usize,
     start: (usize, usize),
     end: (usize, usize),
     dx: isize,
     dy: isize,
-) -> Vec<u64> {
-    let mut best = vec![0; width * height];
+) -> Sweep {
+    let mut best = vec![None; width * height];
+    let mut prev = vec![None; width * height];
 
     let mut sy = start.1 as isize;
     while sy != end.1 as isize + dy {
@@ -20,16 +51,31 @@ fn dp(
             let x = sx as usize;
 
             let a = if sx != start.0 as isize {
-                best[y * width + (sx - dx) as usize]
+                Some(y * width + (sx - dx) as usize)
             } else {
-                0
+                None
             };
             let b = if sy != start.1 as isize {
-                best[(sy - dy) as usize * width + x]
+                Some((sy - dy) as usize * width + x)
             } else {
-                0
+                None
+            };
+
+            // only consider neighbors we can actually reach
+            let a = a.filter(|&a| best[a].is_some());
+            let b = b.filter(|&b| best[b].is_some());
+            let p = match (a, b) {
+                (Some(a), Some(b)) => Some(if best[a] >= best[b] { a } else { b }),
+                _ => a.or(b),
             };
-            best[y * width + x] = grid[y * width + x] + a.max(b);
+
+            let i = y * width + x;
+            if let Some(v) = grid[i]
+                && (p.is_some() || (sx == start.0 as isize && sy == start.1 as isize))
+            {
+                best[i] = Some(v + p.map_or(0, |p| best[p].unwrap()));
+                prev[i] = p;
+            }
 
             sx += dx;
         }
@@ -37,84 +83,249 @@ fn dp(
         sy += dy;
     }
 
-    best
+    Sweep { best, prev }
+}
+
+/// The direction in which route 1 (top left to bottom right) passes the
+/// intersection cell. Route 2 (bottom left to top right) passes it in the
+/// other direction.
+#[derive(Debug)]
+enum Orientation {
+    /// Route 1 passes from top to bottom, route 2 from left to right
+    //
+    //       v
+    //      R_1
+    // ->R_2-|-R_2->
+    //      R_1
+    //       v
+    Vertical,
+
+    /// Route 1 passes from left to right, route 2 from bottom to top
+    //
+    //       ^
+    //      R_2
+    // ->R_1-|-R_1->
+    //      R_2
+    //       ^
+    Horizontal,
+}
+
+impl Orientation {
+    /// Returns the cells before and after the intersection cell `i` on route
+    /// 1 and route 2
+    fn neighbors(&self, i: usize, width: usize) -> (usize, usize, usize, usize) {
+        match self {
+            Orientation::Vertical => (i - width, i + width, i - 1, i + 1),
+            Orientation::Horizontal => (i - 1, i + 1, i + width, i - width),
+        }
+    }
+}
+
+/// The best pair of crossing routes
+struct Crossing {
+    /// The sum of both routes
+    value: u64,
+
+    /// The intersection cell
+    cell: (usize, usize),
+
+    orientation: Orientation,
+
+    /// The cells of route 1 from the top left to the bottom right corner
+    route1: Vec<usize>,
+
+    /// The cells of route 2 from the bottom left to the top right corner
+    route2: Vec<usize>,
+}
+
+/// Renders the grid with the given routes. Cells of route 1 are marked with
+/// `1`, cells of route 2 with `2`, the intersection with `X`, walls with `#`,
+/// and all other cells with `.`.
+fn render(grid: &[Option<u64>], width: usize, route1: &[usize], route2: &[usize]) -> String {
+    let mut cells = grid
+        .iter()
+        .map(|c| if c.is_some() { b'.' } else { b'#' })
+        .collect::<Vec<_>>();
+    for &c in route1 {
+        cells[c] = b'1';
+    }
+    for &c in route2 {
+        cells[c] = if cells[c] == b'1' { b'X' } else { b'2' };
+    }
+    let mut result = String::new();
+    for row in cells.chunks(width) {
+        result.push_str(&String::from_utf8_lossy(row));
+        result.push('\n');
+    }
+    result
+}
+
+/// Multiplies all values. Uses checked arithmetic and only switches to a big
+/// integer if the product does not fit into a `u64` anymore.
+fn product(values: &[u64]) -> UBig {
+    let mut result = 1u64;
+    for (i, &v) in values.iter().enumerate() {
+        match result.checked_mul(v) {
+            Some(r) => result = r,
+            None => {
+                return values[i..]
+                    .iter()
+                    .fold(UBig::from(result), |acc, &v| acc * UBig::from(v));
+            }
+        }
+    }
+    UBig::from(result)
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").expect("Could not read file");
 
-    let mut total1 = 1;
-    let mut total2 = 1;
-    for b in input.split("\n\n") {
-        let lines = b.lines().collect::<Vec<_>>();
-        let width = lines[0].len();
-        let height = lines.len();
-        let grid = lines
-            .into_iter()
-            .flat_map(|l| l.bytes().map(|b| (b - b'0') as u64))
-            .collect::<Vec<_>>();
-
-        // perform BFS from each corner to its diagonally opposite one top left
-        // to bottom right:
-        let best_top_left = dp(&grid, width, height, (0, 0), (width - 1, height - 1), 1, 1);
-
-        // bottom right to top left:
-        let best_bottom_right = dp(
-            &grid,
-            width,
-            height,
-            (width - 1, height - 1),
-            (0, 0),
-            -1,
-            -1,
-        );
-
-        // bottom left to top right
-        let best_bottom_left = dp(&grid, width, height, (0, height - 1), (width - 1, 0), 1, -1);
-
-        // top right to bottom left
-        let best_top_right = dp(&grid, width, height, (width - 1, 0), (0, height - 1), -1, 1);
-
-        // part 1
-        total1 *= best_top_left[height * width - 1];
-
-        // part 2 - for every possible intersection point between the paths,
-        // compute the maximum from each corner to the intersection point
-        let mut max = 0;
-        for y in 1..height - 1 {
-            for x in 1..width - 1 {
-                let center = grid[y * width + x];
-
-                //       v
-                //      R_1
-                // ->R_2-|-R_2->
-                //      R_1
-                //       v
-                {
-                    let max1_1 = best_top_left[(y - 1) * width + x];
-                    let max1_2 = best_bottom_right[(y + 1) * width + x];
-                    let max2_1 = best_bottom_left[y * width + x - 1];
-                    let max2_2 = best_top_right[y * width + x + 1];
-                    max = max.max(center * 2 + max1_1 + max1_2 + max2_1 + max2_2);
+    // all blocks are independent, so we can solve them in parallel
+    let blocks = input.split("\n\n").collect::<Vec<_>>();
+    let results = blocks
+        .into_par_iter()
+        .enumerate()
+        .map(|(bi, b)| {
+            let lines = b.lines().collect::<Vec<_>>();
+            let width = lines[0].len();
+            let height = lines.len();
+            let grid = lines
+                .into_iter()
+                .flat_map(|l| {
+                    l.bytes().map(|b| match b {
+                        b'#' => None,
+                        b'.' => Some(0),
+                        b'0'..=b'9' => Some((b - b'0') as u64),
+                        _ => panic!("Invalid tile: {}", b as char),
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            // perform BFS from each corner to its diagonally opposite one (in
+            // parallel, since the sweeps are independent)
+            let ((best_top_left, best_bottom_right), (best_bottom_left, best_top_right)) =
+                rayon::join(
+                    || {
+                        rayon::join(
+                            // top left to bottom right
+                            || dp(&grid, width, height, (0, 0), (width - 1, height - 1), 1, 1),
+                            // bottom right to top left
+                            || {
+                                dp(
+                                    &grid,
+                                    width,
+                                    height,
+                                    (width - 1, height - 1),
+                                    (0, 0),
+                                    -1,
+                                    -1,
+                                )
+                            },
+                        )
+                    },
+                    || {
+                        rayon::join(
+                            // bottom left to top right
+                            || dp(&grid, width, height, (0, height - 1), (width - 1, 0), 1, -1),
+                            // top right to bottom left
+                            || dp(&grid, width, height, (width - 1, 0), (0, height - 1), -1, 1),
+                        )
+                    },
+                );
+
+            // part 1 - if there is no path, the block contributes 0
+            let part1 = best_top_left.best[height * width - 1];
+            let value1 = part1.unwrap_or(0);
+
+            // part 2 - for every possible intersection point between the
+            // paths, compute the maximum from each corner to the intersection
+            // point
+            let mut best: Option<(u64, usize, Orientation)> = None;
+            for y in 1..height - 1 {
+                for x in 1..width - 1 {
+                    let i = y * width + x;
+                    let Some(center) = grid[i] else {
+                        continue;
+                    };
+
+                    for orientation in [Orientation::Vertical, Orientation::Horizontal] {
+                        // skip this orientation if any of the neighbors cannot
+                        // be reached from its corner
+                        let (r1_1, r1_2, r2_1, r2_2) = orientation.neighbors(i, width);
+                        let (Some(max1_1), Some(max1_2), Some(max2_1), Some(max2_2)) = (
+                            best_top_left.best[r1_1],
+                            best_bottom_right.best[r1_2],
+                            best_bottom_left.best[r2_1],
+                            best_top_right.best[r2_2],
+                        ) else {
+                            continue;
+                        };
+                        let value = center * 2 + max1_1 + max1_2 + max2_1 + max2_2;
+                        if best.as_ref().is_none_or(|b| value > b.0) {
+                            best = Some((value, i, orientation));
+                        }
+                    }
                 }
+            }
+            let value2 = best.as_ref().map_or(0, |b| b.0);
 
-                //       ^
-                //      R_2
-                // ->R_1-|-R_1->
-                //      R_2
-                //       ^
-                {
-                    let max1_1 = best_top_left[y * width + x - 1];
-                    let max1_2 = best_bottom_right[y * width + x + 1];
-                    let max2_1 = best_bottom_left[(y + 1) * width + x];
-                    let max2_2 = best_top_right[(y - 1) * width + x];
-                    max = max.max(center * 2 + max1_1 + max1_2 + max2_1 + max2_2);
+            // reconstruct both routes from the back-pointers
+            let best = best.map(|(value, i, orientation)| {
+                let (r1_1, r1_2, r2_1, r2_2) = orientation.neighbors(i, width);
+                let mut route1 = best_top_left.path_from(r1_1);
+                route1.reverse();
+                route1.push(i);
+                route1.extend(best_bottom_right.path_from(r1_2));
+                let mut route2 = best_bottom_left.path_from(r2_1);
+                route2.reverse();
+                route2.push(i);
+                route2.extend(best_top_right.path_from(r2_2));
+                Crossing {
+                    value,
+                    cell: (i % width, i / width),
+                    orientation,
+                    route1,
+                    route2,
                 }
+            });
+
+            let mut details = String::new();
+            details.push_str(&format!(
+                "Block {bi}: {} (part 1), {} (part 2)\n",
+                value1, value2
+            ));
+            if part1.is_some() {
+                let mut route = best_top_left.path_from(height * width - 1);
+                route.reverse();
+                details.push_str(&render(&grid, width, &route, &[]));
+            } else {
+                details.push_str("The bottom right corner cannot be reached from the top left\n");
             }
-        }
-        total2 *= max;
-    }
+            if let Some(b) = best {
+                details.push_str(&format!(
+                    "Intersection at ({}, {}), route 1 {:?}, sum {}\n",
+                    b.cell.0, b.cell.1, b.orientation, b.value
+                ));
+                details.push_str(&render(&grid, width, &b.route1, &b.route2));
+            } else {
+                details.push_str("There are no crossing routes between the four corners\n");
+            }
+
+            (value1, value2, details)
+        })
+        .collect::<Vec<_>>();
+
+    let values1 = results.iter().map(|r| r.0).collect::<Vec<_>>();
+    let values2 = results.iter().map(|r| r.1).collect::<Vec<_>>();
+    let details = results.iter().map(|r| r.2.as_str()).collect::<String>();
+
+    // the results of all blocks are multiplied, which can overflow
+    let total1 = product(&values1);
+    let total2 = product(&values2);
 
     println!("{total1}");
     println!("{total2}");
+
+    println!();
+    print!("{details}");
 }
@@ -1,7 +1,17 @@
-use std::fs;
+use std::{cmp::Ordering, collections::BinaryHeap, env, fmt, fs};
 
+use dashu_int::IBig;
 use rustc_hash::FxHashMap;
 
+use crate::{
+    polygon::Polygon,
+    sphere::{Cuboid, Point3, Sphere},
+};
+
+mod polygon;
+mod sphere;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 struct Point {
     x: i64,
     y: i64,
@@ -31,17 +41,112 @@ struct Rect {
     max_y: i64,
 }
 
+impl Circle {
+    /// Returns the bounding box of the circle as (min_x, min_y, max_x, max_y).
+    /// Saturates at the bounds of `i64`.
+    fn bounds(&self) -> (i64, i64, i64, i64) {
+        (
+            self.x.saturating_sub(self.r),
+            self.y.saturating_sub(self.r),
+            self.x.saturating_add(self.r),
+            self.y.saturating_add(self.r),
+        )
+    }
+}
+
+/// A node of the quadtree or octree searched by [`find_deepest`]
+trait Region: Copy + fmt::Display {
+    type Point;
+
+    /// The regions [`Region::subdivide`] splits this one into
+    type Children: IntoIterator<Item = Self>;
+
+    /// The number of bits needed to store the index of a child
+    const BITS: u32;
+
+    fn is_empty(&self) -> bool;
+
+    /// Checks if the region contains only a single point
+    fn is_point(&self) -> bool;
+
+    /// Returns the point with the smallest coordinates
+    fn first(&self) -> Self::Point;
+
+    /// Returns the number of points in the region (saturates at `u128::MAX`)
+    fn n_points(&self) -> u128;
+
+    fn subdivide(&self) -> Self::Children;
+}
+
+/// A shape with an axis-aligned bounding box in `D` dimensions
+trait Bounded<const D: usize> {
+    /// Returns the minimum and maximum coordinates of the bounding box
+    fn bounds(&self) -> ([i64; D], [i64; D]);
+}
+
 impl Rect {
     fn width(&self) -> i64 {
-        self.max_x - self.min_x
+        self.max_x.saturating_sub(self.min_x)
     }
 
     fn height(&self) -> i64 {
-        self.max_y - self.min_y
+        self.max_y.saturating_sub(self.min_y)
     }
 
-    fn area(&self) -> i64 {
-        self.width() * self.height()
+    /// Returns the four corner points of the rectangle (`max_x` and `max_y` are
+    /// exclusive)
+    fn corners(&self) -> [Point; 4] {
+        [
+            Point::from((self.min_x, self.min_y)),
+            Point::from((self.max_x - 1, self.min_y)),
+            Point::from((self.min_x, self.max_y - 1)),
+            Point::from((self.max_x - 1, self.max_y - 1)),
+        ]
+    }
+}
+
+impl fmt::Display for Rect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "x = {}..{}, y = {}..{}",
+            self.min_x, self.max_x, self.min_y, self.max_y
+        )
+    }
+}
+
+impl Region for Rect {
+    type Point = Point;
+    type Children = [Rect; 4];
+    const BITS: u32 = 2;
+
+    fn is_empty(&self) -> bool {
+        self.width() <= 0 || self.height() <= 0
+    }
+
+    fn is_point(&self) -> bool {
+        self.width() == 1 && self.height() == 1
+    }
+
+    fn first(&self) -> Point {
+        Point::from((self.min_x, self.min_y))
+    }
+
+    fn n_points(&self) -> u128 {
+        self.width() as u128 * self.height() as u128
+    }
+
+    /// Splits the rectangle into four quadrants
+    fn subdivide(&self) -> [Rect; 4] {
+        let mid = |a: i64, b: i64| ((a as i128 + b as i128) / 2) as i64;
+        let mid_x = mid(self.min_x, self.max_x);
+        let mid_y = mid(self.min_y, self.max_y);
+        [
+            Rect::from((self.min_x, self.min_y, mid_x, mid_y)),
+            Rect::from((mid_x, self.min_y, self.max_x, mid_y)),
+            Rect::from((self.min_x, mid_y, mid_x, self.max_y)),
+            Rect::from((mid_x, mid_y, self.max_x, self.max_y)),
+        ]
     }
 }
 
@@ -56,16 +161,37 @@ impl From<(i64, i64, i64, i64)> for Rect {
     }
 }
 
+/// Computes the squared distance between two points given by the differences
+/// of their coordinates. The differences of two `i64` values always fit into
+/// an `i128` and their squares into a `u128`. The sum saturates, which is fine
+/// since the result is only compared with squared radii below `u128::MAX`.
+fn squared_dist(dx: i128, dy: i128) -> u128 {
+    squared(dx).saturating_add(squared(dy))
+}
+
+/// Squares a value whose absolute value is less than 2^64
+fn squared(v: i128) -> u128 {
+    let a = v.unsigned_abs();
+    a * a
+}
+
 trait Contains<T> {
     fn contains(&self, other: &T) -> bool;
 }
 
 impl Contains<Point> for Circle {
     fn contains(&self, other: &Point) -> bool {
-        let dx = self.x - other.x;
-        let dy = self.y - other.y;
-        let squared_dist = dx * dx + dy * dy;
-        squared_dist < self.r * self.r
+        let dx = self.x as i128 - other.x as i128;
+        let dy = self.y as i128 - other.y as i128;
+        squared_dist(dx, dy) < squared(self.r as i128)
+    }
+}
+
+impl Contains<Rect> for Circle {
+    fn contains(&self, other: &Rect) -> bool {
+        // the circle is convex, so it contains all points of the rectangle if
+        // it contains its corners
+        other.corners().iter().all(|p| self.contains(p))
     }
 }
 
@@ -75,10 +201,9 @@ trait Overlaps<T> {
 
 impl Overlaps<Circle> for Circle {
     fn overlaps(&self, other: &Circle) -> bool {
-        let dx = self.x - other.x;
-        let dy = self.y - other.y;
-        let squared_dist = dx * dx + dy * dy;
-        squared_dist < (self.r + other.r) * (self.r + other.r)
+        let dx = self.x as i128 - other.x as i128;
+        let dy = self.y as i128 - other.y as i128;
+        squared_dist(dx, dy) < squared(self.r as i128 + other.r as i128)
     }
 }
 
@@ -86,9 +211,9 @@ impl Overlaps<Circle> for Rect {
     fn overlaps(&self, other: &Circle) -> bool {
         let x = self.min_x.max(self.max_x.min(other.x));
         let y = self.min_y.max(self.max_y.min(other.y));
-        let dx = x - other.x;
-        let dy = y - other.y;
-        (dx * dx + dy * dy) < other.r * other.r
+        let dx = x as i128 - other.x as i128;
+        let dy = y as i128 - other.y as i128;
+        squared_dist(dx, dy) < squared(other.r as i128)
     }
 }
 
@@ -98,141 +223,860 @@ impl Overlaps<Rect> for Circle {
     }
 }
 
-fn dfs(
-    rect: &Rect,
-    circles: &[Circle],
-    min_overlaps: usize,
-    cache: &mut FxHashMap<Rect, usize>,
-) -> Option<i64> {
-    // if the rectangle contains only one point check if this is the point we're
-    // looking for
-    if rect.width() == 1 && rect.height() == 1 {
-        let count = circles
-            .iter()
-            .filter(|c| c.contains(&Point::from((rect.min_x, rect.min_y))))
-            .count();
-        if count == min_overlaps {
-            return Some(rect.min_x * rect.min_y);
-        }
-        return None;
-    }
-
-    // subdivide rectangle
-    let rect1 = Rect::from((
-        rect.min_x,
-        rect.min_y,
-        (rect.min_x + rect.max_x) / 2,
-        (rect.min_y + rect.max_y) / 2,
-    ));
-    let rect2 = Rect::from((
-        (rect.min_x + rect.max_x) / 2,
-        rect.min_y,
-        rect.max_x,
-        (rect.min_y + rect.max_y) / 2,
-    ));
-    let rect3 = Rect::from((
-        rect.min_x,
-        (rect.min_y + rect.max_y) / 2,
-        (rect.min_x + rect.max_x) / 2,
-        rect.max_y,
-    ));
-    let rect4 = Rect::from((
-        (rect.min_x + rect.max_x) / 2,
-        (rect.min_y + rect.max_y) / 2,
-        rect.max_x,
-        rect.max_y,
-    ));
-
-    // Look in the sub-rectangles for a single point where exactly
-    // `min_overlaps` circles overlap. This can only happen if their count is
-    // larger than or equal to `min_overlaps`. Otherwise, we don't need to go
-    // deeper.
-    for r in [rect1, rect2, rect3, rect4] {
-        // count how many circles overlap with the sub-rectangle
-        let count = if r.area() > 0 {
-            *cache
-                .entry(r)
-                .or_insert_with(|| circles.iter().filter(|c| r.overlaps(c)).count())
-        } else {
-            0
+/// An input shape. Rectangles and diamonds are represented as polygons.
+enum Shape {
+    Circle(Circle),
+    Polygon(Polygon),
+}
+
+impl Shape {
+    /// Parses a line of the input and returns the shape and the first point on
+    /// the line. The syntax determines the type of the shape:
+    ///
+    /// * `<x, y> r=R` - a circle with center (x, y) and radius R
+    /// * `<x, y> d=D` - a diamond containing all points whose Manhattan
+    ///   distance to (x, y) is less than D
+    /// * `<x1, y1> <x2, y2>` - an axis-aligned rectangle with the given
+    ///   opposite corners
+    /// * `<x1, y1> <x2, y2> <x3, y3> ...` - a convex polygon with the given
+    ///   vertices
+    ///
+    /// Like circles, all shapes are open, i.e. they do not contain the points
+    /// on their boundary.
+    fn parse(l: &str) -> (Point, Shape) {
+        let (points, param) = l
+            .rsplit_once('>')
+            .unwrap_or_else(|| panic!("Invalid shape: {l}"));
+        let points = points
+            .split('>')
+            .map(|p| {
+                let (x, y) = p.trim()[1..].split_once(',').unwrap();
+                Point::from((x.trim().parse().unwrap(), y.trim().parse().unwrap()))
+            })
+            .collect::<Vec<_>>();
+        let p = points[0];
+
+        let shape = match (points.len(), param.trim().split_once('=')) {
+            (1, Some(("r", r))) => Shape::Circle(Circle {
+                x: p.x,
+                y: p.y,
+                r: r.parse().unwrap(),
+            }),
+            (1, Some(("d", d))) => {
+                let d = d.parse::<i64>().unwrap();
+                Shape::Polygon(Polygon::new(vec![
+                    Point::from((p.x - d, p.y)),
+                    Point::from((p.x, p.y - d)),
+                    Point::from((p.x + d, p.y)),
+                    Point::from((p.x, p.y + d)),
+                ]))
+            }
+            (2, None) => {
+                let q = points[1];
+                Shape::Polygon(Polygon::new(vec![
+                    p,
+                    Point::from((q.x, p.y)),
+                    q,
+                    Point::from((p.x, q.y)),
+                ]))
+            }
+            (3.., None) => Shape::Polygon(Polygon::new(points)),
+            _ => panic!("Invalid shape: {l}"),
         };
 
-        // go deeper if necessary
-        if count >= min_overlaps
-            && let Some(result) = dfs(&r, circles, min_overlaps, cache)
-        {
-            return Some(result);
+        (p, shape)
+    }
+}
+
+impl Bounded<2> for Shape {
+    fn bounds(&self) -> ([i64; 2], [i64; 2]) {
+        let (min_x, min_y, max_x, max_y) = match self {
+            Shape::Circle(c) => c.bounds(),
+            Shape::Polygon(p) => p.bounds(),
+        };
+        ([min_x, min_y], [max_x, max_y])
+    }
+}
+
+impl Contains<Point> for Shape {
+    fn contains(&self, other: &Point) -> bool {
+        match self {
+            Shape::Circle(c) => c.contains(other),
+            Shape::Polygon(p) => p.contains(other),
+        }
+    }
+}
+
+impl Contains<Rect> for Shape {
+    fn contains(&self, other: &Rect) -> bool {
+        match self {
+            Shape::Circle(c) => c.contains(other),
+            Shape::Polygon(p) => p.contains(other),
         }
     }
+}
 
-    None
+impl Overlaps<Rect> for Shape {
+    fn overlaps(&self, other: &Rect) -> bool {
+        match self {
+            Shape::Circle(c) => c.overlaps(other),
+            Shape::Polygon(p) => p.overlaps(other),
+        }
+    }
 }
 
-fn main() {
-    let input = fs::read_to_string("input.txt").expect("Could not read file");
+impl Overlaps<Shape> for Shape {
+    fn overlaps(&self, other: &Shape) -> bool {
+        match (self, other) {
+            (Shape::Circle(a), Shape::Circle(b)) => a.overlaps(b),
+            (Shape::Circle(a), Shape::Polygon(b)) => a.overlaps(b),
+            (Shape::Polygon(a), Shape::Circle(b)) => a.overlaps(b),
+            (Shape::Polygon(a), Shape::Polygon(b)) => a.overlaps(b),
+        }
+    }
+}
 
-    let mut circles = Vec::new();
-    for l in input.lines() {
-        let mut parts = l.split_ascii_whitespace();
-        let x = parts.next().unwrap();
-        let y = parts.next().unwrap();
-        let r = parts.next().unwrap();
-        let x = x[1..x.len() - 1].parse::<i64>().unwrap();
-        let y = y[0..y.len() - 1].parse::<i64>().unwrap();
-        let r = r[2..].parse::<i64>().unwrap();
-        circles.push(Circle { x, y, r });
+/// Shapes whose bounding box covers more grid cells than this are not put
+/// into the spatial index but compared with all other shapes directly
+const MAX_CELLS_PER_SHAPE: i128 = 64;
+
+/// Counts for each shape how many other shapes it overlaps. To avoid comparing
+/// every pair of shapes, we put them into a uniform grid whose cells are about
+/// as large as an average shape. Each shape is added to all cells its bounding
+/// box covers, so only shapes sharing a cell need to be compared.
+fn count_overlaps<const D: usize, S: Bounded<D> + Overlaps<S>>(shapes: &[S]) -> Vec<usize> {
+    let mut overlaps = vec![0; shapes.len()];
+    if shapes.is_empty() {
+        return overlaps;
     }
 
-    // part 1
-    let mut overlaps = vec![0; circles.len()];
-    for (i, a) in circles.iter().enumerate() {
-        for (j, b) in circles.iter().enumerate().skip(i + 1) {
-            if a.overlaps(b) {
+    let size = |s: &S| {
+        let (min, max) = s.bounds();
+        (0..D)
+            .map(|d| max[d] as i128 - min[d] as i128)
+            .max()
+            .unwrap_or(0)
+    };
+    let cell_size = (shapes.iter().map(size).sum::<i128>() / shapes.len() as i128)
+        .clamp(1, i64::MAX as i128) as i64;
+    let cell = |v: i64| v.div_euclid(cell_size);
+
+    // build the index and collect shapes that are too large for it
+    let mut grid: FxHashMap<[i64; D], Vec<usize>> = FxHashMap::default();
+    let mut large = Vec::new();
+    for (i, c) in shapes.iter().enumerate() {
+        let (min, max) = c.bounds();
+        let (min_c, max_c) = (min.map(cell), max.map(cell));
+        let n_cells = (0..D).fold(1i128, |n, d| {
+            n.saturating_mul(max_c[d] as i128 - min_c[d] as i128 + 1)
+        });
+        if n_cells > MAX_CELLS_PER_SHAPE {
+            large.push(i);
+            continue;
+        }
+
+        // visit all cells between `min_c` and `max_c` like an odometer
+        let mut c = min_c;
+        loop {
+            grid.entry(c).or_default().push(i);
+            let Some(d) = (0..D).find(|&d| c[d] < max_c[d]) else {
+                break;
+            };
+            c[d] += 1;
+            c[..d].copy_from_slice(&min_c[..d]);
+        }
+    }
+
+    // Compare all shapes sharing a cell. Two shapes can share several cells,
+    // so we only compare them in the cell containing the lower left corner of
+    // the intersection of their bounding boxes.
+    for (key, indices) in &grid {
+        for (k, &i) in indices.iter().enumerate() {
+            let a = &shapes[i];
+            for &j in &indices[k + 1..] {
+                let b = &shapes[j];
+                let (min_a, min_b) = (a.bounds().0, b.bounds().0);
+                if (0..D).all(|d| cell(min_a[d].max(min_b[d])) == key[d]) && a.overlaps(b) {
+                    overlaps[i] += 1;
+                    overlaps[j] += 1;
+                }
+            }
+        }
+    }
+
+    // compare large shapes with all others
+    let mut is_large = vec![false; shapes.len()];
+    for &i in &large {
+        is_large[i] = true;
+    }
+    for &i in &large {
+        for j in 0..shapes.len() {
+            // pairs of large shapes must only be counted once
+            if j == i || (is_large[j] && j < i) {
+                continue;
+            }
+            if shapes[i].overlaps(&shapes[j]) {
                 overlaps[i] += 1;
                 overlaps[j] += 1;
             }
         }
     }
+
+    overlaps
+}
+
+/// The maximum number of bytes the priority queue of [`find_deepest`] may
+/// occupy. Regions that do not fit into the queue anymore are searched
+/// depth-first. The stack of this search is not included in the limit and
+/// needs at most [`max_stack_bytes`] in addition.
+const MAX_QUEUE_BYTES: usize = 256 << 20;
+
+/// The maximum number of regions at the maximum depth [`find_deepest`]
+/// collects. Enumerating all of them can take very long for large shapes, so
+/// we stop afterwards and only report lower bounds.
+const MAX_REGIONS: usize = 1000;
+
+/// A region in the priority queue of [`find_deepest`]
+#[derive(PartialEq, Eq)]
+struct QueueEntry<R> {
+    /// The number of shapes overlapping the region. This is an upper bound
+    /// for the number of shapes overlapping any point in it.
+    count: usize,
+
+    /// The number of shapes containing the region completely
+    contained: usize,
+
+    /// The indices of all shapes overlapping the region but not containing it
+    /// completely. Only these shapes need to be tested for the children of
+    /// the region. If there are none, `count` is the exact number of shapes
+    /// at every point in the region. This is always the case for single
+    /// points.
+    partial: Vec<u32>,
+
+    /// The position of the region in the tree as a sequence of child indices
+    /// ([`Region::BITS`] bits per level, starting at the most significant bit).
+    /// Used to break ties in depth-first order. Supports up to 64 levels of an
+    /// octree, which is enough for every bounding box that fits into an `i64`.
+    path: [u64; 3],
+
+    /// The depth of the region in the tree
+    depth: u32,
+
+    region: R,
+}
+
+impl<R: Region> QueueEntry<R> {
+    /// Creates an entry for the given region. `contained` is the number of
+    /// shapes containing the parent region and `candidates` are the indices
+    /// of the shapes partially overlapping it.
+    fn new<S>(
+        region: R,
+        path: [u64; 3],
+        depth: u32,
+        mut contained: usize,
+        candidates: &[u32],
+        shapes: &[S],
+    ) -> Self
+    where
+        S: Contains<R::Point> + Contains<R> + Overlaps<R>,
+    {
+        let mut partial = Vec::new();
+        if region.is_point() {
+            let p = region.first();
+            contained += candidates
+                .iter()
+                .filter(|&&i| shapes[i as usize].contains(&p))
+                .count();
+        } else if !region.is_empty() {
+            for &i in candidates {
+                let s = &shapes[i as usize];
+                if s.overlaps(&region) {
+                    if s.contains(&region) {
+                        contained += 1;
+                    } else {
+                        partial.push(i);
+                    }
+                }
+            }
+        } else {
+            contained = 0;
+        }
+
+        Self {
+            count: contained + partial.len(),
+            contained,
+            partial,
+            path,
+            depth,
+            region,
+        }
+    }
+
+    /// Checks if all points in the region have the same depth
+    fn is_uniform(&self) -> bool {
+        self.partial.is_empty()
+    }
+
+    /// Returns the path of the child with the given index
+    fn child_path(&self, i: usize) -> [u64; 3] {
+        let mut path = self.path;
+        for b in 0..R::BITS {
+            if (i >> (R::BITS - 1 - b)) & 1 == 1 {
+                let pos = (self.depth * R::BITS + b) as usize;
+                path[pos / 64] |= 1 << (63 - pos % 64);
+            }
+        }
+        path
+    }
+
+    /// Creates entries for all children of this region
+    fn children<S>(&self, shapes: &[S]) -> impl Iterator<Item = QueueEntry<R>>
+    where
+        S: Contains<R::Point> + Contains<R> + Overlaps<R>,
+    {
+        self.region
+            .subdivide()
+            .into_iter()
+            .enumerate()
+            .map(move |(i, r)| {
+                let path = self.child_path(i);
+                let (contained, partial) = (self.contained, &self.partial);
+                QueueEntry::new(r, path, self.depth + 1, contained, partial, shapes)
+            })
+    }
+
+    /// Returns the number of bytes occupied by this entry
+    fn bytes(&self) -> usize {
+        size_of::<Self>() + self.partial.capacity() * size_of::<u32>()
+    }
+}
+
+impl<R: Eq> Ord for QueueEntry<R> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.count
+            .cmp(&other.count)
+            .then_with(|| other.path.cmp(&self.path))
+    }
+}
+
+impl<R: Eq> PartialOrd for QueueEntry<R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The result of [`find_deepest`]
+struct Deepest<R: Region> {
+    /// The first point at the maximum depth in depth-first order of the tree
+    first: R::Point,
+
+    /// The path of the region containing `first` (see [`QueueEntry::path`])
+    first_path: [u64; 3],
+
+    /// The maximum number of shapes overlapping at a single point
+    depth: usize,
+
+    /// Disjoint regions covering the points at the maximum depth (at most
+    /// [`MAX_REGIONS`])
+    regions: Vec<R>,
+
+    /// The number of points in `regions` (saturates at `u128::MAX`)
+    n_points: u128,
+
+    /// Specifies whether `regions` covers all points at the maximum depth.
+    /// Otherwise, `n_points` is only a lower bound.
+    complete: bool,
+
+    /// The maximum number of bytes occupied by the priority queue and the
+    /// stack of the depth-first search during the search
+    peak_bytes: usize,
+}
+
+/// Records a uniform region found by [`find_deepest`]
+fn record<R: Region>(result: &mut Option<Deepest<R>>, e: &QueueEntry<R>) {
+    if result.as_ref().is_none_or(|r| e.count > r.depth) {
+        *result = Some(Deepest {
+            first: e.region.first(),
+            first_path: e.path,
+            depth: e.count,
+            regions: Vec::new(),
+            n_points: 0,
+            complete: true,
+            peak_bytes: 0,
+        });
+    }
+    let r = result.as_mut().unwrap();
+    if e.path < r.first_path {
+        r.first = e.region.first();
+        r.first_path = e.path;
+    }
+    if r.regions.len() < MAX_REGIONS {
+        r.regions.push(e.region);
+        r.n_points = r.n_points.saturating_add(e.region.n_points());
+    } else {
+        r.complete = false;
+    }
+}
+
+/// Returns an upper bound for the number of bytes the stack of the depth-first
+/// search in [`find_deepest`] can occupy. A tree has at most 64 levels (see
+/// [`QueueEntry::path`]), and the stack holds at most the unvisited siblings
+/// of each region on the current path. Each entry lists at most all shapes,
+/// and its vector may have twice the capacity it needs.
+fn max_stack_bytes<R: Region>(n_shapes: usize) -> usize {
+    let entries = 1 + 64 * ((1 << R::BITS) - 1);
+    entries * (size_of::<QueueEntry<R>>() + (2 * n_shapes + 4) * size_of::<u32>())
+}
+
+/// Checks if [`find_deepest`] can skip the given region. This is the case if
+/// it cannot contain points at the maximum depth. It is also the case if it
+/// can, but we do not collect any more regions and all of its points come
+/// after the first point at the maximum depth.
+fn can_skip<R: Region>(
+    e: &QueueEntry<R>,
+    result: &mut Option<Deepest<R>>,
+    all_deepest: bool,
+) -> bool {
+    if e.count == 0 {
+        return true;
+    }
+    let Some(r) = result else {
+        return false;
+    };
+    if e.count < r.depth {
+        return true;
+    }
+    if e.count == r.depth
+        && e.path > r.first_path
+        && (!all_deepest || r.regions.len() >= MAX_REGIONS)
+    {
+        r.complete = false;
+        return true;
+    }
+    false
+}
+
+/// Finds the points inside the given region where the most shapes overlap.
+///
+/// The search is a branch-and-bound over a priority queue of regions (nodes of
+/// a quadtree in 2D or an octree in 3D) ordered by the number of shapes
+/// overlapping them. Since this number is an upper bound for all points
+/// inside, the first uniform region (see [`QueueEntry::is_uniform`]) we take
+/// from the queue has the maximum depth. Ties are broken in depth-first order
+/// of the tree, so the first point of this region is the first point at the
+/// maximum depth. Every region is counted only once and only tests the shapes
+/// that partially overlap its parent.
+///
+/// If the queue would grow beyond `max_queue_bytes` (usually
+/// [`MAX_QUEUE_BYTES`]), we search the subtree of the region we could not add
+/// depth-first instead. Its regions may be found out of order, so we always
+/// keep the one with the smallest path. The stack of the depth-first search
+/// comes on top of the queue, but it is small (see [`max_stack_bytes`]).
+///
+/// If `all_deepest` is set, we continue until there are no more regions
+/// that could contain points at the maximum depth or until we have collected
+/// [`MAX_REGIONS`]. Either way, the first point and the maximum depth do not
+/// depend on how many regions we collect.
+fn find_deepest<R, S>(
+    root: R,
+    shapes: &[S],
+    all_deepest: bool,
+    max_queue_bytes: usize,
+) -> Option<Deepest<R>>
+where
+    R: Region + Eq,
+    S: Contains<R::Point> + Contains<R> + Overlaps<R>,
+{
+    let all = (0..shapes.len())
+        .map(|i| u32::try_from(i).expect("Too many shapes"))
+        .collect::<Vec<_>>();
+    let root = QueueEntry::new(root, [0; 3], 0, 0, &all, shapes);
+    drop(all);
+
+    let mut bytes = root.bytes();
+    let mut peak_bytes = bytes;
+    let mut queue = BinaryHeap::new();
+    queue.push(root);
+
+    // Regions with the same count are taken from the queue in depth-first
+    // order. Once we can skip one, we can skip all of the remaining ones.
+    let mut result: Option<Deepest<R>> = None;
+    while let Some(e) = queue.pop() {
+        bytes -= e.bytes();
+        if can_skip(&e, &mut result, all_deepest) {
+            break;
+        }
+
+        if e.is_uniform() {
+            record(&mut result, &e);
+            continue;
+        }
+
+        for child in e.children(shapes) {
+            if child.count == 0 {
+                continue;
+            }
+            if bytes + child.bytes() <= max_queue_bytes {
+                bytes += child.bytes();
+                peak_bytes = peak_bytes.max(bytes);
+                queue.push(child);
+                continue;
+            }
+
+            // the queue is full - search the subtree depth-first
+            let mut stack_bytes = child.bytes();
+            let mut stack = vec![child];
+            while let Some(e) = stack.pop() {
+                stack_bytes -= e.bytes();
+                if can_skip(&e, &mut result, all_deepest) {
+                    continue;
+                }
+                if e.is_uniform() {
+                    record(&mut result, &e);
+                    continue;
+                }
+
+                // push the children in reverse order, so we visit them in
+                // depth-first order of the tree
+                let children = e.children(shapes).collect::<Vec<_>>();
+                for c in children.into_iter().rev() {
+                    stack_bytes += c.bytes();
+                    stack.push(c);
+                }
+                peak_bytes = peak_bytes.max(bytes + stack_bytes);
+            }
+        }
+    }
+
+    if let Some(r) = &mut result {
+        r.peak_bytes = peak_bytes;
+    }
+    result
+}
+
+/// Solves both parts for the given shapes. `anchors` contains the first point
+/// of each shape's input line, `product` multiplies the coordinates of a
+/// point, and `root` creates a square or cube spanning the given range in all
+/// dimensions. If `all_deepest` is set, we also report the points at the
+/// maximum depth.
+fn solve<const D: usize, R, S>(
+    anchors: &[R::Point],
+    shapes: &[S],
+    product: impl Fn(&R::Point) -> IBig,
+    root: impl Fn(i64, i64) -> R,
+    all_deepest: bool,
+) where
+    R: Region + Eq,
+    S: Bounded<D> + Overlaps<S> + Contains<R::Point> + Contains<R> + Overlaps<R>,
+{
+    // part 1
+    let overlaps = count_overlaps(shapes);
     let max = overlaps
         .into_iter()
         .enumerate()
         .max_by_key(|(_, o)| *o)
         .unwrap();
-    println!("{}", circles[max.0].x * circles[max.0].y + max.1);
+    println!("{}", product(&anchors[max.0]) + IBig::from(max.1));
 
     // part 2...
 
-    // find bounding box
-    let mut min_x = i64::MAX;
-    let mut max_x = i64::MIN;
-    let mut min_y = i64::MAX;
-    let mut max_y = i64::MIN;
-    for c in &circles {
-        min_x = min_x.min(c.x - c.r);
-        min_y = min_y.min(c.y - c.r);
-        max_x = max_x.max(c.x + c.r);
-        max_y = max_y.max(c.y + c.r);
-    }
-
-    // make bounding box square
-    let min = min_x.min(min_y);
-    let max = max_x.max(max_y);
-
-    // try to find a single point where `min_overlaps` circles overlap. Try this
-    // again and again until we found the point. Since we start with the highest
-    // value, the first point we find, will be the one we're looking for.
-    let mut cache = FxHashMap::default();
-    let mut min_overlaps = circles.len();
-    while min_overlaps > 0 {
-        if let Some(r) = dfs(
-            &Rect::from((min, min, max, max)),
-            &circles,
-            min_overlaps,
-            &mut cache,
-        ) {
-            println!("{r}");
-            break;
+    // find bounding box and make it square
+    let mut min = i64::MAX;
+    let mut max = i64::MIN;
+    for s in shapes {
+        let (s_min, s_max) = s.bounds();
+        min = s_min.into_iter().fold(min, i64::min);
+        max = s_max.into_iter().fold(max, i64::max);
+    }
+
+    if let Some(deepest) = find_deepest(root(min, max), shapes, all_deepest, MAX_QUEUE_BYTES) {
+        println!("{}", product(&deepest.first));
+
+        println!();
+        println!(
+            "Peak memory of the search: {} bytes (limit {MAX_QUEUE_BYTES} bytes for the queue \
+             plus at most {} bytes for the depth-first stack)",
+            deepest.peak_bytes,
+            max_stack_bytes::<R>(shapes.len())
+        );
+        if all_deepest {
+            if !deepest.complete {
+                println!(
+                    "Stopped after {MAX_REGIONS} regions, the following counts are only lower bounds"
+                );
+            }
+            let at_least = if deepest.complete { "" } else { "at least " };
+            println!(
+                "{at_least}{} point(s) with {} overlapping shapes in {at_least}{} region(s):",
+                deepest.n_points,
+                deepest.depth,
+                deepest.regions.len()
+            );
+            for r in &deepest.regions {
+                println!("  {r}");
+            }
+            if !deepest.complete {
+                println!("  ...");
+            }
+        }
+    }
+}
+
+fn main() {
+    // `--all-deepest` reports all points at the maximum depth in part 2 (up to
+    // `MAX_REGIONS` regions)
+    let mut all_deepest = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--all-deepest" => all_deepest = true,
+            _ => panic!("Unknown argument: {arg}"),
+        }
+    }
+
+    let input = fs::read_to_string("input.txt").expect("Could not read file");
+
+    // inputs whose points have three coordinates contain spheres
+    let is_3d = input
+        .lines()
+        .next()
+        .is_some_and(|l| l.split('>').next().unwrap().matches(',').count() == 2);
+
+    if is_3d {
+        let (anchors, spheres): (Vec<_>, Vec<_>) = input.lines().map(Sphere::parse).unzip();
+        solve(
+            &anchors,
+            &spheres,
+            |p: &Point3| IBig::from(p.x) * IBig::from(p.y) * IBig::from(p.z),
+            |min, max| Cuboid::from((min, min, min, max, max, max)),
+            all_deepest,
+        );
+    } else {
+        let (anchors, shapes): (Vec<_>, Vec<_>) = input.lines().map(Shape::parse).unzip();
+        solve(
+            &anchors,
+            &shapes,
+            |p: &Point| IBig::from(p.x as i128 * p.y as i128),
+            |min, max| Rect::from((min, min, max, max)),
+            all_deepest,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circle(x: i64, y: i64, r: i64) -> Circle {
+        Circle { x, y, r }
+    }
+
+    /// Generates a pseudo-random number in `0..n`
+    fn random(seed: &mut u64, n: i64) -> i64 {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (*seed >> 33) as i64 % n
+    }
+
+    /// Generates a line of the input describing a random shape. Most shapes
+    /// are small, but some are large enough to bypass the spatial index.
+    fn random_shape(seed: &mut u64, range: i64, max_size: i64) -> String {
+        let x = random(seed, 2 * range) - range;
+        let y = random(seed, 2 * range) - range;
+        let size = if random(seed, 10) == 0 {
+            random(seed, 15 * max_size) + 1
+        } else {
+            random(seed, max_size) + 1
+        };
+        let (a, b) = (random(seed, size) + 1, random(seed, size) + 1);
+        match random(seed, 4) {
+            0 => format!("<{x}, {y}> r={size}"),
+            1 => format!("<{x}, {y}> d={size}"),
+            2 => format!("<{x}, {y}> <{}, {}>", x + a, y + b),
+            _ => format!("<{x}, {y}> <{}, {y}> <{x}, {}>", x + a, y + b),
+        }
+    }
+
+    /// Counts the overlaps by comparing every pair of shapes
+    fn brute_overlaps<S: Overlaps<S>>(shapes: &[S]) -> Vec<usize> {
+        let mut overlaps = vec![0; shapes.len()];
+        for (i, a) in shapes.iter().enumerate() {
+            for (j, b) in shapes.iter().enumerate().skip(i + 1) {
+                if a.overlaps(b) {
+                    overlaps[i] += 1;
+                    overlaps[j] += 1;
+                }
+            }
+        }
+        overlaps
+    }
+
+    #[test]
+    fn points_on_the_rim_are_outside() {
+        let c = circle(0, 0, 5);
+        assert!(!c.contains(&Point::from((3, 4))));
+        assert!(!c.contains(&Point::from((0, -5))));
+        assert!(c.contains(&Point::from((3, 3))));
+        assert!(c.contains(&Point::from((4, 2))));
+    }
+
+    #[test]
+    fn points_on_the_rim_of_large_circles_are_outside() {
+        let c = circle(3_000_000_000, -3_000_000_000, 3_000_000_000);
+        assert!(!c.contains(&Point::from((0, -3_000_000_000))));
+        assert!(c.contains(&Point::from((1, -3_000_000_000))));
+        assert!(!c.contains(&Point::from((6_000_000_000, -3_000_000_000))));
+        assert!(c.contains(&Point::from((5_999_999_999, -3_000_000_000))));
+
+        let c = circle(i64::MAX, i64::MAX, i64::MAX);
+        assert!(!c.contains(&Point::from((0, i64::MAX))));
+        assert!(c.contains(&Point::from((1, i64::MAX))));
+        assert!(!c.contains(&Point::from((i64::MIN, i64::MIN))));
+    }
+
+    #[test]
+    fn tangent_circles_do_not_overlap() {
+        assert!(!circle(0, 0, 5).overlaps(&circle(10, 0, 5)));
+        assert!(!circle(0, 0, 5).overlaps(&circle(6, 8, 5)));
+        assert!(circle(0, 0, 5).overlaps(&circle(9, 0, 5)));
+        assert!(circle(0, 0, 5).overlaps(&circle(6, 7, 5)));
+    }
+
+    #[test]
+    fn tangent_large_circles_do_not_overlap() {
+        let a = circle(-3_000_000_000, 0, 3_000_000_000);
+        assert!(!a.overlaps(&circle(3_000_000_000, 0, 3_000_000_000)));
+        assert!(a.overlaps(&circle(3_000_000_000, 0, 3_000_000_001)));
+
+        let a = circle(i64::MIN, i64::MIN, i64::MAX);
+        assert!(!a.overlaps(&circle(i64::MAX, i64::MAX, i64::MAX)));
+        assert!(a.overlaps(&circle(0, i64::MIN, i64::MAX)));
+    }
+
+    #[test]
+    fn rectangles_touching_the_rim() {
+        let c = circle(0, 0, 5);
+        assert!(!c.overlaps(&Rect::from((5, 0, 6, 1))));
+        assert!(c.overlaps(&Rect::from((4, 0, 6, 1))));
+        assert!(!c.contains(&Rect::from((0, 0, 6, 1))));
+        assert!(c.contains(&Rect::from((0, 0, 5, 1))));
+    }
+
+    #[test]
+    fn count_overlaps_agrees_with_brute_force() {
+        let mut seed = 1;
+        for _ in 0..300 {
+            let n = random(&mut seed, 40) + 1;
+            let shapes = (0..n)
+                .map(|_| Shape::parse(&random_shape(&mut seed, 100, 20)).1)
+                .collect::<Vec<_>>();
+            assert_eq!(count_overlaps(&shapes), brute_overlaps(&shapes));
+        }
+    }
+
+    #[test]
+    fn count_overlaps_agrees_with_brute_force_in_3d() {
+        let mut seed = 2;
+        for _ in 0..300 {
+            let n = random(&mut seed, 40) + 1;
+            let spheres = (0..n)
+                .map(|_| {
+                    let [x, y, z] = [0; 3].map(|_| random(&mut seed, 100) - 50);
+                    let r = random(&mut seed, 30) + 1;
+                    Sphere::parse(&format!("<{x}, {y}, {z}>, r={r}")).1
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(count_overlaps(&spheres), brute_overlaps(&spheres));
+        }
+    }
+
+    /// Returns the path from the root to the given point as a list of child
+    /// indices. Comparing these lists gives the depth-first order of the tree.
+    fn path_to(root: Rect, p: Point) -> Vec<usize> {
+        let mut region = root;
+        let mut path = Vec::new();
+        while !region.is_point() {
+            let (i, child) = region
+                .subdivide()
+                .into_iter()
+                .enumerate()
+                .find(|(_, c)| {
+                    !c.is_empty()
+                        && (c.min_x..c.max_x).contains(&p.x)
+                        && (c.min_y..c.max_y).contains(&p.y)
+                })
+                .unwrap();
+            path.push(i);
+            region = child;
+        }
+        path
+    }
+
+    /// Finds the maximum depth and the first point at this depth by testing
+    /// every point of the root region
+    fn brute_deepest(root: Rect, shapes: &[Shape]) -> Option<(usize, Point, u128)> {
+        let mut best: Option<(usize, Vec<usize>, Point)> = None;
+        let mut n_points = 0;
+        for y in root.min_y..root.max_y {
+            for x in root.min_x..root.max_x {
+                let p = Point::from((x, y));
+                let depth = shapes.iter().filter(|s| s.contains(&p)).count();
+                if depth == 0 || best.as_ref().is_some_and(|b| depth < b.0) {
+                    continue;
+                }
+                if best.as_ref().is_none_or(|b| depth > b.0) {
+                    n_points = 0;
+                }
+                n_points += 1;
+                let path = path_to(root, p);
+                if best.as_ref().is_none_or(|b| depth > b.0 || path < b.1) {
+                    best = Some((depth, path, p));
+                }
+            }
+        }
+        best.map(|(depth, _, p)| (depth, p, n_points))
+    }
+
+    #[test]
+    fn find_deepest_agrees_with_brute_force() {
+        let mut seed = 3;
+        for _ in 0..200 {
+            let n = random(&mut seed, 10) + 1;
+            let shapes = (0..n)
+                .map(|_| Shape::parse(&random_shape(&mut seed, 15, 4)).1)
+                .collect::<Vec<_>>();
+            let mut min = i64::MAX;
+            let mut max = i64::MIN;
+            for s in &shapes {
+                let (s_min, s_max) = Bounded::<2>::bounds(s);
+                min = s_min.into_iter().fold(min, i64::min);
+                max = s_max.into_iter().fold(max, i64::max);
+            }
+            let root = Rect::from((min, min, max, max));
+            let expected = brute_deepest(root, &shapes);
+
+            // the second run is too small for the queue and searches almost
+            // everything depth-first
+            for max_queue_bytes in [MAX_QUEUE_BYTES, 256] {
+                for all_deepest in [false, true] {
+                    let got = find_deepest(root, &shapes, all_deepest, max_queue_bytes);
+                    let got = got.map(|d| {
+                        let max_bytes = max_queue_bytes + max_stack_bytes::<Rect>(shapes.len());
+                        assert!(d.peak_bytes <= max_bytes);
+
+                        // the number of points is only known if we collected
+                        // all regions
+                        let n_points = if all_deepest && d.complete {
+                            d.n_points
+                        } else {
+                            expected.map_or(0, |e| e.2)
+                        };
+                        (d.depth, d.first, n_points)
+                    });
+                    assert!(
+                        got == expected,
+                        "max_queue_bytes = {max_queue_bytes}, all_deepest = {all_deepest}"
+                    );
+                }
+            }
         }
-        min_overlaps -= 1;
     }
 }
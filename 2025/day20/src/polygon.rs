@@ -0,0 +1,190 @@
+use dashu_int::IBig;
+
+use crate::{Circle, Contains, Overlaps, Point, Rect, Region};
+
+/// A convex polygon. Like circles, polygons are open, i.e. points on their
+/// boundary are not inside.
+pub struct Polygon {
+    /// The vertices in counter-clockwise order
+    vertices: Vec<Point>,
+
+    /// The bounding box as (min_x, min_y, max_x, max_y)
+    bounds: (i64, i64, i64, i64),
+}
+
+/// Computes the cross product of the vectors `a - o` and `b - o`. It is
+/// positive if `b` lies to the left of the line from `o` through `a`.
+fn cross(o: &Point, a: &Point, b: &Point) -> IBig {
+    let (ax, ay) = diff(a, o);
+    let (bx, by) = diff(b, o);
+    products(ax, by, -ay, bx)
+}
+
+/// Computes the dot product of the vectors `a - o` and `b - o`
+fn dot(o: &Point, a: &Point, b: &Point) -> IBig {
+    let (ax, ay) = diff(a, o);
+    let (bx, by) = diff(b, o);
+    products(ax, bx, ay, by)
+}
+
+fn diff(a: &Point, b: &Point) -> (i128, i128) {
+    (a.x as i128 - b.x as i128, a.y as i128 - b.y as i128)
+}
+
+/// Computes `a * b + c * d`. The differences of two `i64` values always fit
+/// into an `i128` but their products may not. We only switch to big integers
+/// if one of the factors does not fit into an `i64`.
+fn products(a: i128, b: i128, c: i128, d: i128) -> IBig {
+    let small = |v: i128| i64::try_from(v).is_ok();
+    if small(a) && small(b) && small(c) && small(d) {
+        IBig::from(a * b + c * d)
+    } else {
+        IBig::from(a) * IBig::from(b) + IBig::from(c) * IBig::from(d)
+    }
+}
+
+impl Polygon {
+    /// Creates a polygon from the given vertices in clockwise or
+    /// counter-clockwise order. Panics if the polygon is not strictly convex.
+    pub fn new(mut vertices: Vec<Point>) -> Self {
+        vertices.dedup();
+        while vertices.len() > 1 && vertices.first() == vertices.last() {
+            vertices.pop();
+        }
+        assert!(
+            vertices.len() >= 3,
+            "A polygon needs at least three distinct vertices"
+        );
+
+        let n = vertices.len();
+        let area = (1..n - 1)
+            .map(|i| cross(&vertices[0], &vertices[i], &vertices[i + 1]))
+            .sum::<IBig>();
+        assert!(area != IBig::ZERO, "Polygon must not be degenerate");
+        if area < IBig::ZERO {
+            vertices.reverse();
+        }
+
+        // all turns must go to the left and the edges must only go around once
+        // (a pentagram also turns left at every vertex)
+        let convex = (0..n).all(|i| {
+            cross(&vertices[i], &vertices[(i + 1) % n], &vertices[(i + 2) % n]) > IBig::ZERO
+        });
+        let signs = (0..n)
+            .map(|i| vertices[(i + 1) % n].x.cmp(&vertices[i].x))
+            .filter(|s| s.is_ne())
+            .collect::<Vec<_>>();
+        let sign_changes = (0..signs.len())
+            .filter(|&i| signs[i] != signs[(i + 1) % signs.len()])
+            .count();
+        assert!(convex && sign_changes <= 2, "Polygon must be convex");
+
+        let bounds = vertices.iter().fold(
+            (i64::MAX, i64::MAX, i64::MIN, i64::MIN),
+            |(min_x, min_y, max_x, max_y), v| {
+                (
+                    min_x.min(v.x),
+                    min_y.min(v.y),
+                    max_x.max(v.x),
+                    max_y.max(v.y),
+                )
+            },
+        );
+
+        Self { vertices, bounds }
+    }
+
+    /// Returns the bounding box of the polygon as (min_x, min_y, max_x, max_y)
+    pub fn bounds(&self) -> (i64, i64, i64, i64) {
+        self.bounds
+    }
+
+    /// Iterates over all edges in counter-clockwise order
+    fn edges(&self) -> impl Iterator<Item = (&Point, &Point)> {
+        self.vertices
+            .iter()
+            .zip(self.vertices.iter().cycle().skip(1))
+    }
+
+    /// Checks if there is an edge such that all given points lie on it or to
+    /// its right, i.e. if the edge separates the points from the interior of
+    /// the polygon
+    fn separates(&self, points: &[Point]) -> bool {
+        self.edges()
+            .any(|(a, b)| points.iter().all(|p| cross(a, b, p) <= IBig::ZERO))
+    }
+}
+
+impl Contains<Point> for Polygon {
+    fn contains(&self, other: &Point) -> bool {
+        self.edges().all(|(a, b)| cross(a, b, other) > IBig::ZERO)
+    }
+}
+
+impl Contains<Rect> for Polygon {
+    fn contains(&self, other: &Rect) -> bool {
+        // the polygon is convex, so it contains all points of the rectangle if
+        // it contains its corners
+        other.corners().iter().all(|p| self.contains(p))
+    }
+}
+
+impl Overlaps<Rect> for Polygon {
+    /// Checks if the polygon overlaps any point of the rectangle, including
+    /// the ones on its border. According to the separating axis theorem, the
+    /// two do not overlap if an edge of one of them separates them.
+    fn overlaps(&self, other: &Rect) -> bool {
+        if other.is_empty() {
+            return false;
+        }
+        let (min_x, min_y, max_x, max_y) = self.bounds();
+        if max_x <= other.min_x
+            || max_y <= other.min_y
+            || other.max_x - 1 <= min_x
+            || other.max_y - 1 <= min_y
+        {
+            return false;
+        }
+        !self.separates(&other.corners())
+    }
+}
+
+impl Overlaps<Polygon> for Polygon {
+    fn overlaps(&self, other: &Polygon) -> bool {
+        !self.separates(&other.vertices) && !other.separates(&self.vertices)
+    }
+}
+
+impl Overlaps<Circle> for Polygon {
+    /// The circle and the polygon overlap if the distance between the center
+    /// and the polygon (including its boundary) is less than the radius
+    fn overlaps(&self, other: &Circle) -> bool {
+        if other.r <= 0 {
+            return false;
+        }
+        let c = Point::from((other.x, other.y));
+        if self.edges().all(|(a, b)| cross(a, b, &c) >= IBig::ZERO) {
+            return true;
+        }
+
+        // compare the squared distance to the closest point of each edge
+        let r2 = IBig::from(other.r).pow(2);
+        self.edges().any(|(a, b)| {
+            let t = dot(a, b, &c);
+            let len2 = dot(a, b, b);
+            if t <= IBig::ZERO {
+                dot(a, &c, &c) < r2
+            } else if t >= len2 {
+                dot(b, &c, &c) < r2
+            } else {
+                cross(a, b, &c).pow(2) < &r2 * len2
+            }
+        })
+    }
+}
+
+impl Overlaps<Polygon> for Circle {
+    fn overlaps(&self, other: &Polygon) -> bool {
+        other.overlaps(self)
+    }
+}
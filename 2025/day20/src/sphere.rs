@@ -0,0 +1,221 @@
+use std::fmt;
+
+use crate::{Bounded, Contains, Overlaps, Region, squared};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Point3 {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+impl From<(i64, i64, i64)> for Point3 {
+    fn from(value: (i64, i64, i64)) -> Self {
+        Self {
+            x: value.0,
+            y: value.1,
+            z: value.2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Sphere {
+    x: i64,
+    y: i64,
+    z: i64,
+    r: i64,
+}
+
+/// An axis-aligned box. The maximum coordinates are exclusive.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Cuboid {
+    min_x: i64,
+    min_y: i64,
+    min_z: i64,
+    max_x: i64,
+    max_y: i64,
+    max_z: i64,
+}
+
+impl Sphere {
+    /// Parses a line of the form `<x, y, z>, r=R` and returns the sphere and
+    /// its center
+    pub fn parse(l: &str) -> (Point3, Sphere) {
+        let (center, r) = l
+            .split_once('>')
+            .unwrap_or_else(|| panic!("Invalid sphere: {l}"));
+        let mut coords = center.trim()[1..]
+            .split(',')
+            .map(|c| c.trim().parse::<i64>().unwrap());
+        let (x, y, z) = (
+            coords.next().unwrap(),
+            coords.next().unwrap(),
+            coords.next().unwrap(),
+        );
+        let r = r
+            .trim()
+            .trim_start_matches(',')
+            .trim()
+            .strip_prefix("r=")
+            .unwrap_or_else(|| panic!("Invalid sphere: {l}"))
+            .parse()
+            .unwrap();
+        (Point3::from((x, y, z)), Sphere { x, y, z, r })
+    }
+}
+
+impl Bounded<3> for Sphere {
+    /// Saturates at the bounds of `i64`
+    fn bounds(&self) -> ([i64; 3], [i64; 3]) {
+        let c = [self.x, self.y, self.z];
+        (
+            c.map(|v| v.saturating_sub(self.r)),
+            c.map(|v| v.saturating_add(self.r)),
+        )
+    }
+}
+
+impl Cuboid {
+    fn width(&self) -> i64 {
+        self.max_x.saturating_sub(self.min_x)
+    }
+
+    fn height(&self) -> i64 {
+        self.max_y.saturating_sub(self.min_y)
+    }
+
+    fn depth(&self) -> i64 {
+        self.max_z.saturating_sub(self.min_z)
+    }
+
+    /// Returns the eight corner points of the box
+    fn corners(&self) -> [Point3; 8] {
+        std::array::from_fn(|i| {
+            let pick = |bit: usize, min: i64, max: i64| if i & bit == 0 { min } else { max - 1 };
+            Point3::from((
+                pick(1, self.min_x, self.max_x),
+                pick(2, self.min_y, self.max_y),
+                pick(4, self.min_z, self.max_z),
+            ))
+        })
+    }
+}
+
+impl From<(i64, i64, i64, i64, i64, i64)> for Cuboid {
+    fn from(value: (i64, i64, i64, i64, i64, i64)) -> Self {
+        Self {
+            min_x: value.0,
+            min_y: value.1,
+            min_z: value.2,
+            max_x: value.3,
+            max_y: value.4,
+            max_z: value.5,
+        }
+    }
+}
+
+impl fmt::Display for Cuboid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "x = {}..{}, y = {}..{}, z = {}..{}",
+            self.min_x, self.max_x, self.min_y, self.max_y, self.min_z, self.max_z
+        )
+    }
+}
+
+impl Region for Cuboid {
+    type Point = Point3;
+    type Children = [Cuboid; 8];
+    const BITS: u32 = 3;
+
+    fn is_empty(&self) -> bool {
+        self.width() <= 0 || self.height() <= 0 || self.depth() <= 0
+    }
+
+    fn is_point(&self) -> bool {
+        self.width() == 1 && self.height() == 1 && self.depth() == 1
+    }
+
+    fn first(&self) -> Point3 {
+        Point3::from((self.min_x, self.min_y, self.min_z))
+    }
+
+    fn n_points(&self) -> u128 {
+        (self.width() as u128 * self.height() as u128).saturating_mul(self.depth() as u128)
+    }
+
+    /// Splits the box into eight octants. Child `i` is in the upper half of
+    /// the x, y, and z axis if bit 0, 1, and 2 of `i` are set, respectively.
+    fn subdivide(&self) -> [Cuboid; 8] {
+        let mid = |a: i64, b: i64| ((a as i128 + b as i128) / 2) as i64;
+        let mid_x = mid(self.min_x, self.max_x);
+        let mid_y = mid(self.min_y, self.max_y);
+        let mid_z = mid(self.min_z, self.max_z);
+        std::array::from_fn(|i| {
+            let (min_x, max_x) = if i & 1 == 0 {
+                (self.min_x, mid_x)
+            } else {
+                (mid_x, self.max_x)
+            };
+            let (min_y, max_y) = if i & 2 == 0 {
+                (self.min_y, mid_y)
+            } else {
+                (mid_y, self.max_y)
+            };
+            let (min_z, max_z) = if i & 4 == 0 {
+                (self.min_z, mid_z)
+            } else {
+                (mid_z, self.max_z)
+            };
+            Cuboid::from((min_x, min_y, min_z, max_x, max_y, max_z))
+        })
+    }
+}
+
+/// Computes the squared distance between two points given by the differences
+/// of their coordinates. Saturates like [`crate::squared_dist`].
+fn squared_dist(dx: i128, dy: i128, dz: i128) -> u128 {
+    squared(dx)
+        .saturating_add(squared(dy))
+        .saturating_add(squared(dz))
+}
+
+impl Contains<Point3> for Sphere {
+    fn contains(&self, other: &Point3) -> bool {
+        let dx = self.x as i128 - other.x as i128;
+        let dy = self.y as i128 - other.y as i128;
+        let dz = self.z as i128 - other.z as i128;
+        squared_dist(dx, dy, dz) < squared(self.r as i128)
+    }
+}
+
+impl Contains<Cuboid> for Sphere {
+    fn contains(&self, other: &Cuboid) -> bool {
+        // the sphere is convex, so it contains all points of the box if it
+        // contains its corners
+        other.corners().iter().all(|p| self.contains(p))
+    }
+}
+
+impl Overlaps<Sphere> for Sphere {
+    fn overlaps(&self, other: &Sphere) -> bool {
+        let dx = self.x as i128 - other.x as i128;
+        let dy = self.y as i128 - other.y as i128;
+        let dz = self.z as i128 - other.z as i128;
+        squared_dist(dx, dy, dz) < squared(self.r as i128 + other.r as i128)
+    }
+}
+
+impl Overlaps<Cuboid> for Sphere {
+    fn overlaps(&self, other: &Cuboid) -> bool {
+        let x = other.min_x.max(other.max_x.min(self.x));
+        let y = other.min_y.max(other.max_y.min(self.y));
+        let z = other.min_z.max(other.max_z.min(self.z));
+        let dx = x as i128 - self.x as i128;
+        let dy = y as i128 - self.y as i128;
+        let dz = z as i128 - self.z as i128;
+        squared_dist(dx, dy, dz) < squared(self.r as i128)
+    }
+}
@@ -65,6 +65,11 @@ impl Signature {
 }
 
 impl Signature {
+    /// Check if there are no nodes on the edge
+    pub fn is_empty(&self) -> bool {
+        self.signature.is_empty()
+    }
+
     /// Get matching nodes between this signature and another signature
     pub fn get_matches(&self, other: &Signature) -> Option<Vec<(Node, Node)>> {
         let mut self_iter = self.signature.iter().copied().enumerate();
@@ -119,11 +124,22 @@ impl Signature {
 pub struct Side {
     /// The signatures of the side's edges ordered by [Direction]
     signatures: [Signature; 4],
+
+    /// The index of the grid this side was created from
+    grid: usize,
+
+    /// The number of clockwise quarter turns the grid has been rotated by
+    rotation: usize,
 }
 
 impl Side {
-    /// Create a new side from the given graph
-    pub fn new(graph: &FxHashMap<Node, Vec<Edge>>, width: usize, height: usize) -> Self {
+    /// Create a new side from the graph of the grid with the given index
+    pub fn new(
+        graph: &FxHashMap<Node, Vec<Edge>>,
+        grid: usize,
+        width: usize,
+        height: usize,
+    ) -> Self {
         let left = graph
             .keys()
             .filter(|n| n.x == 0)
@@ -152,6 +168,8 @@ impl Side {
                 Signature::new(bottom, width, height),
                 Signature::new(left, width, height),
             ],
+            grid,
+            rotation: 0,
         }
     }
 
@@ -160,13 +178,64 @@ impl Side {
         &self.signatures[dir as usize]
     }
 
+    /// Check if no paths cross any of the side's edges. Such a side is not
+    /// connected to its neighbors, so all its rotations are equivalent.
+    pub fn is_blank(&self) -> bool {
+        self.signatures.iter().all(|s| s.is_empty())
+    }
+
+    /// Get the index of the grid this side was created from
+    pub fn get_grid(&self) -> usize {
+        self.grid
+    }
+
+    /// Get the number of clockwise quarter turns the grid has been rotated by
+    pub fn get_rotation(&self) -> usize {
+        self.rotation
+    }
+
     /// Rotate the side so that the edge in direction `from` becomes the edge in
     /// direction `to`
     pub fn to_rotated(&self, from: Direction, to: Direction) -> Self {
+        let turns = (to as isize - from as isize).rem_euclid(4) as usize;
         let mut result = self.clone();
+        result.signatures.rotate_right(turns);
+        result.rotation = (result.rotation + turns) % 4;
         result
-            .signatures
-            .rotate_right((to as isize - from as isize).rem_euclid(4) as usize);
+    }
+
+    /// Get the 24 rotations of the whole cube. Each rotation maps the side at
+    /// index `i` to the side at index `r[i].0` and turns it clockwise by
+    /// `r[i].1` quarter turns.
+    ///
+    /// We derive the rotations from [Side::get_neighbors]: A rotation is fully
+    /// determined by where it moves side 0 and how it turns it. The remaining
+    /// sides follow by walking across the edges, since neighbors must stay
+    /// neighbors.
+    pub fn get_cube_rotations() -> Vec<[(usize, usize); 6]> {
+        let mut result = Vec::new();
+        for target in 0..6 {
+            for turns in 0..4 {
+                let mut rotation = [None; 6];
+                rotation[0] = Some((target, turns));
+                let mut stack = vec![0];
+                while let Some(i) = stack.pop() {
+                    let (ri, rt) = rotation[i].unwrap();
+                    for (from_dir, to_side, to_dir) in Side::get_neighbors(i) {
+                        if rotation[to_side].is_some() {
+                            continue;
+                        }
+                        // the neighbors are ordered by direction
+                        let (_, r_side, r_dir) =
+                            Side::get_neighbors(ri)[(from_dir as usize + rt) % 4];
+                        let r_turns = (r_dir as usize + 4 - to_dir as usize) % 4;
+                        rotation[to_side] = Some((r_side, r_turns));
+                        stack.push(to_side);
+                    }
+                }
+                result.push(rotation.map(|r| r.unwrap()));
+            }
+        }
         result
     }
 
@@ -239,3 +308,31 @@ impl Side {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cube_rotations_keep_neighbors() {
+        let rotations = Side::get_cube_rotations();
+        let mut distinct = rotations.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 24);
+        let identity: [(usize, usize); 6] = std::array::from_fn(|i| (i, 0));
+        assert!(rotations.contains(&identity));
+
+        for rotation in &rotations {
+            for i in 0..6 {
+                let (ri, rt) = rotation[i];
+                for (from_dir, to_side, to_dir) in Side::get_neighbors(i) {
+                    let (_, r_side, r_dir) = Side::get_neighbors(ri)[(from_dir as usize + rt) % 4];
+                    let (ti, tt) = rotation[to_side];
+                    assert_eq!(r_side, ti);
+                    assert_eq!(r_dir as usize, (to_dir as usize + tt) % 4);
+                }
+            }
+        }
+    }
+}
@@ -5,11 +5,13 @@ use rustc_hash::{FxHashMap, FxHashSet};
 use crate::{
     cube::{Direction, Side},
     grid::{DIRS, Edge, Get, Grid, Has, Node, Set as _},
+    render::render,
     unionfind::{Set, find, union},
 };
 
 mod cube;
 mod grid;
+mod render;
 mod unionfind;
 
 /// Converts a grid to a graph where every outlet, junction, and grid exit is a
@@ -151,24 +153,37 @@ fn clean_graph(full_graph: &mut FxHashMap<Node, Vec<Edge>>, grids: &[Grid<u8>])
         .map(|(node, edges)| (*node, edges.clone()))
         .collect::<Vec<_>>();
     while let Some((node, edges)) = queue.pop() {
-        let v0 = full_graph.get_mut(&edges[0].to).unwrap();
+        // the node may already have been removed together with its last
+        // neighbor (e.g. if a path connects two exits without outlets)
+        if !full_graph.contains_key(&node) {
+            continue;
+        }
+
+        let to = edges[0].to;
+        let v0 = full_graph.get_mut(&to).unwrap();
         v0.remove(v0.iter().position(|o| o.to == node).unwrap());
         if v0.is_empty() {
-            full_graph.remove(&edges[0].to);
-        } else if v0.len() == 1 {
-            queue.push((edges[0].to, v0.clone()));
+            full_graph.remove(&to);
+        } else if v0.len() == 1 && grids[to.grid].get(to.x, to.y) != b'O' {
+            // outlets must stay in the graph even if they become leaves
+            queue.push((to, v0.clone()));
         }
         full_graph.remove(&node);
     }
 }
 
 /// Find all possible configurations in which we can put graphs on each side of
-/// the cube and rotate them so they are connected. We assume that all sides are
-/// somehow connected. Otherwise, the problem would not be solvable, so this
-/// assumption is safe. However, this also means that it won't work for the
-/// example (Which actually does not match the problem statement saying "On each
-/// square grid, there are two outlet 'O's". The example contains square grids
-/// without any outlet.).
+/// the cube and rotate them so they are connected. A side can be put next to
+/// an assigned one if any of the paths crossing the edge between them match or
+/// if no paths cross this edge at all. The latter is necessary for sides that
+/// are not connected to the others (e.g. in the example, which contains square
+/// grids without any outlet). A blank side, i.e. one without any path crossing
+/// its edges, is only put in its original rotation, since turning it would not
+/// change any connection.
+///
+/// The first grid is always put unrotated on side 1, which rules out most
+/// rotations of the whole cube. The result can still contain equivalent
+/// configurations, see [get_distinct_configurations].
 fn get_cube_configurations(
     sides: &[Side],
     graphs: &[FxHashMap<Node, Vec<Edge>>],
@@ -178,7 +193,7 @@ fn get_cube_configurations(
     queue.push_back((
         vec![
             None,
-            Some(Side::new(&graphs[0], grids[0].width, grids[0].height)),
+            Some(Side::new(&graphs[0], 0, grids[0].width, grids[0].height)),
             None,
             None,
             None,
@@ -216,16 +231,21 @@ fn get_cube_configurations(
                         continue;
                     }
 
-                    // check if any edge signature matches
+                    // check if any edge signature matches or if there is no
+                    // path crossing the edge
                     let ss = s.get_signature(from_dir);
+                    let blank = other.is_blank();
                     for od in [
                         Direction::Top,
                         Direction::Right,
                         Direction::Bottom,
                         Direction::Left,
                     ] {
+                        if blank && od != to_dir {
+                            continue;
+                        }
                         let os = sides[oi].get_signature(od);
-                        if ss.get_matches(os).is_some() {
+                        if ss.get_matches(os).is_some() || (ss.is_empty() && os.is_empty()) {
                             // rotate the graph and assign it to the configuration
                             let new_side = other.to_rotated(od, to_dir);
                             let mut new_configuration = configuration.clone();
@@ -248,6 +268,37 @@ fn get_cube_configurations(
         .collect()
 }
 
+/// Remove configurations that are equivalent to another one. Blank sides are
+/// not connected to anything, so swapping them changes no connection, and if
+/// the first grid is blank, rotating the whole cube does not move anything
+/// that matters either. We therefore identify every configuration by the grid
+/// and rotation of each of its non-blank sides and use the smallest such key
+/// among all 24 cube rotations. Equivalent configurations have equal keys and
+/// lead to the same number of cells.
+fn get_distinct_configurations(configurations: Vec<Vec<Side>>) -> Vec<Vec<Side>> {
+    let rotations = Side::get_cube_rotations();
+    let mut seen = FxHashSet::default();
+    configurations
+        .into_iter()
+        .filter(|configuration| {
+            let key = rotations
+                .iter()
+                .map(|rotation| {
+                    let mut key = [None; 6];
+                    for (s, &(to, turns)) in configuration.iter().zip(rotation) {
+                        if !s.is_blank() {
+                            key[to] = Some((s.get_grid(), (s.get_rotation() + turns) % 4));
+                        }
+                    }
+                    key
+                })
+                .min()
+                .unwrap();
+            seen.insert(key)
+        })
+        .collect()
+}
+
 /// Find all possible connections between every pair of cube sides
 fn get_connections(configuration: &[Side]) -> Vec<(Node, Node)> {
     let mut result = Vec::new();
@@ -401,13 +452,26 @@ fn main() {
     // convert graphs to cube sides
     let sides = graphs
         .iter()
-        .map(|g| Side::new(g, grids[0].width, grids[0].height))
+        .enumerate()
+        .map(|(gi, g)| Side::new(g, gi, grids[0].width, grids[0].height))
         .collect::<Vec<_>>();
 
     // find valid cube configurations
     let valid_configurations = get_cube_configurations(&sides, &graphs, &grids);
 
+    // only solve each distinct configuration once
+    let n_raw = valid_configurations.len();
+    let valid_configurations = get_distinct_configurations(valid_configurations);
+    let n_distinct = valid_configurations.len();
+
+    // not every grid necessarily has two outlets
+    let outlets = grids
+        .iter()
+        .map(|g| g.grid.iter().filter(|&&c| c == b'O').count())
+        .sum::<usize>();
+
     let mut min = usize::MAX;
+    let mut best = None;
     for configuration in valid_configurations {
         // put every graph into a new full graph
         let mut full_graph: FxHashMap<Node, Vec<Edge>> = FxHashMap::default();
@@ -435,13 +499,35 @@ fn main() {
         // and that are not outlets
         compress_graph(&mut full_graph, &grids);
 
+        // every outlet must be part of the graph, otherwise we might find a
+        // spanning tree that does not connect all of them
+        let connected_outlets = full_graph
+            .keys()
+            .filter(|n| grids[n.grid].get(n.x, n.y) == b'O')
+            .count();
+        if connected_outlets < outlets {
+            continue;
+        }
+
         if let Some(steps) = single_mst(full_graph) {
             // What we get from the MST is the number of steps required to get
             // from a starting outlet to any other outlet, but we need the
             // number of grid cells we have to fill. Add 1 for the starting
             // outlet and then subtract the total number of outlets.
-            min = min.min((steps + 1) - grids.len() * 2);
+            let cells = (steps + 1) - outlets;
+            if cells < min {
+                min = cells;
+                best = Some(configuration);
+            }
         }
     }
     println!("{min}");
+
+    println!();
+    println!("Configurations: {n_raw} found, {n_distinct} distinct");
+
+    if let Some(best) = best {
+        println!();
+        print!("{}", render(&best, &grids));
+    }
 }
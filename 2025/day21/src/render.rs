@@ -0,0 +1,103 @@
+use crate::{
+    cube::{Direction, Side},
+    grid::{Get, Grid, Node},
+};
+
+/// The positions of the cube sides in the net (column, row). See
+/// [Side::get_neighbors].
+const NET: [(usize, usize); 6] = [(1, 0), (1, 1), (0, 1), (2, 1), (1, 2), (1, 3)];
+
+/// Characters used to label connections along the seams
+const LABELS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// Get the position of the given node after rotating its grid clockwise by
+/// the given number of quarter turns
+fn rotate(node: Node, size: usize, rotation: usize) -> (usize, usize) {
+    let (mut x, mut y) = (node.x, node.y);
+    for _ in 0..rotation {
+        (x, y) = (size - y - 1, x);
+    }
+    (x, y)
+}
+
+/// Render a cube configuration as its unfolded net. Every grid is placed and
+/// rotated according to its side. The sides are separated by a gap of one
+/// character. Each connection between two sides is marked with the same label
+/// in the gap next to both of its nodes. Sides that are adjacent in the net
+/// share the gap, so the labels of a correct connection coincide there.
+pub fn render(configuration: &[Side], grids: &[Grid<u8>]) -> String {
+    // all grids are square and have the same size
+    let size = grids[0].width;
+    let width = 3 * (size + 1) + 1;
+    let height = 4 * (size + 1) + 1;
+    let mut canvas = vec![b' '; width * height];
+
+    // get the position of a node on the canvas
+    let position = |side: usize, node: Node| {
+        let (x, y) = rotate(node, size, configuration[side].get_rotation());
+        let (cx, cy) = NET[side];
+        (1 + cx * (size + 1) + x, 1 + cy * (size + 1) + y)
+    };
+
+    // draw the grids
+    for (i, side) in configuration.iter().enumerate() {
+        let grid = &grids[side.get_grid()];
+        for y in 0..size {
+            for x in 0..size {
+                let node = Node {
+                    x,
+                    y,
+                    grid: side.get_grid(),
+                };
+                let (px, py) = position(i, node);
+                canvas[py * width + px] = grid.get(x, y);
+            }
+        }
+    }
+
+    // mark connections next to their nodes (every seam appears twice in the
+    // list of neighbors, so we only look at it from the side with the lower
+    // index)
+    let mut label = 0;
+    for (i, s) in configuration.iter().enumerate() {
+        for (from_dir, to_side, to_dir) in Side::get_neighbors(i) {
+            if to_side < i {
+                continue;
+            }
+            let Some(matches) = s
+                .get_signature(from_dir)
+                .get_matches(configuration[to_side].get_signature(to_dir))
+            else {
+                continue;
+            };
+            for (a, b) in matches {
+                let c = LABELS[label % LABELS.len()];
+                label += 1;
+                for (side, node, dir) in [(i, a, from_dir), (to_side, b, to_dir)] {
+                    let (px, py) = position(side, node);
+                    let (px, py) = match dir {
+                        Direction::Top => (px, py - 1),
+                        Direction::Right => (px + 1, py),
+                        Direction::Bottom => (px, py + 1),
+                        Direction::Left => (px - 1, py),
+                    };
+                    canvas[py * width + px] = c;
+                }
+            }
+        }
+    }
+
+    let mut result = String::new();
+    for (i, side) in configuration.iter().enumerate() {
+        result.push_str(&format!(
+            "Side {i}: grid {}, rotated by {} degrees\n",
+            side.get_grid(),
+            side.get_rotation() * 90
+        ));
+    }
+    for row in canvas.chunks(width) {
+        result.push_str(String::from_utf8_lossy(row).trim_end());
+        result.push('\n');
+    }
+    result
+}
@@ -1,5 +1,6 @@
 use std::{cmp::Ordering, collections::BinaryHeap, fs};
 
+use dashu_int::IBig;
 use rayon::prelude::*;
 
 // Right, Down, Left, Up
@@ -117,6 +118,23 @@ fn dijkstra(
     (best, path)
 }
 
+/// Multiplies all values. Uses checked arithmetic and only switches to a big
+/// integer if the product does not fit into an `i64` anymore.
+fn product(values: &[i64]) -> IBig {
+    let mut result = 1i64;
+    for (i, &v) in values.iter().enumerate() {
+        match result.checked_mul(v) {
+            Some(r) => result = r,
+            None => {
+                return values[i..]
+                    .iter()
+                    .fold(IBig::from(result), |acc, &v| acc * IBig::from(v));
+            }
+        }
+    }
+    IBig::from(result)
+}
+
 fn main() {
     // We can solve both parts simultaneously. For part 2, we use Suurballe's
     // algorithm (https://en.wikipedia.org/wiki/Suurballe%27s_algorithm), to
@@ -134,10 +152,10 @@ fn main() {
     // algorithm (Dijkstra).
 
     let input = fs::read_to_string("input.txt").expect("Could not read file");
-    let blocks = input.split("\n\n");
+    let blocks = input.split("\n\n").collect::<Vec<_>>();
 
     let (sums1, sums2): (Vec<i64>, Vec<i64>) = blocks
-        .par_bridge()
+        .into_par_iter()
         .map(|b| {
             // parse grid
             let lines = b.lines().collect::<Vec<_>>();
@@ -295,6 +313,12 @@ fn main() {
         })
         .unzip();
 
-    println!("{}", sums1.into_iter().product::<i64>());
-    println!("{}", sums2.into_iter().product::<i64>());
+    // the results of all blocks are multiplied, which can overflow
+    println!("{}", product(&sums1));
+    println!("{}", product(&sums2));
+
+    println!();
+    for (i, (s1, s2)) in sums1.iter().zip(&sums2).enumerate() {
+        println!("Block {i}: {s1} (part 1), {s2} (part 2)");
+    }
 }